@@ -0,0 +1,149 @@
+use crate::grading::{normalize, Grade, Grader};
+use rand::{seq::SliceRandom, Rng};
+use std::collections::BTreeSet;
+
+// Options for multiple-choice questions, made from the answers of other cards.
+
+pub const OPTIONS: usize = 4;
+
+// Rough shape of an answer, so that a year is not offered next to a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Word,
+    Phrase,
+    Sentence,
+}
+
+fn kind(text: &str) -> Kind {
+    let words = text.split_whitespace().count();
+    if text.chars().any(|c| c.is_ascii_digit()) {
+        Kind::Number
+    } else if words <= 1 {
+        Kind::Word
+    } else if words <= 4 {
+        Kind::Phrase
+    } else {
+        Kind::Sentence
+    }
+}
+
+// How unlike the answer a candidate is: the difference in length relative to
+// the longer of the two, plus one if they are of a different kind.
+fn distance(answer: &str, candidate: &str) -> f64 {
+    let (a, b) = (answer.chars().count(), candidate.chars().count());
+    let length = a.abs_diff(b) as f64 / a.max(b).max(1) as f64;
+    let kind = if kind(answer) == kind(candidate) {
+        0.0
+    } else {
+        1.0
+    };
+    length + kind
+}
+
+// The answer and up to OPTIONS - 1 other candidates, in random order.
+// Distractors are drawn from the candidates most like the answer; candidates
+// the grader would accept as the answer are left out. None if no candidate
+// is left.
+pub fn options(
+    answer: &str,
+    candidates: &[&str],
+    grader: &Grader,
+    rng: &mut impl Rng,
+) -> Option<Vec<String>> {
+    let mut seen = BTreeSet::from([normalize(answer)]);
+    let mut pool: Vec<&str> = candidates
+        .iter()
+        .copied()
+        .filter(|c| !c.trim().is_empty() && grader.grade(c, answer) == Grade::Wrong)
+        .filter(|c| seen.insert(normalize(c)))
+        .collect();
+    if pool.is_empty() {
+        return None;
+    }
+    // Shuffled first so that equally close candidates take turns.
+    pool.shuffle(rng);
+    pool.sort_by(|a, b| distance(answer, a).total_cmp(&distance(answer, b)));
+    pool.truncate(2 * (OPTIONS - 1));
+    let mut options: Vec<String> = pool
+        .choose_multiple(rng, OPTIONS - 1)
+        .map(|c| c.to_string())
+        .collect();
+    options.push(answer.to_string());
+    options.shuffle(rng);
+    Some(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const ANSWER: &str = "photosynthesis";
+    const CANDIDATES: &[&str] = &[
+        "Photosynthesis",
+        "photosynthesis.",
+        "  PHOTOSYNTHESIS ",
+        "fotosynthesis",
+        "",
+        "   ",
+        "respiration",
+        "Respiration",
+        "osmosis",
+        "diffusion",
+        "transpiration",
+        "fermentation",
+        "the Calvin cycle",
+        "1953",
+    ];
+
+    #[test]
+    fn only_the_answer_is_accepted() {
+        let grader = Grader::default();
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let options = options(ANSWER, CANDIDATES, &grader, &mut rng).unwrap();
+            assert_eq!(options.len(), OPTIONS);
+            let accepted: Vec<_> = options
+                .iter()
+                .filter(|o| grader.grade(o, ANSWER) != Grade::Wrong)
+                .collect();
+            assert_eq!(accepted, [ANSWER]);
+            let distinct: BTreeSet<_> = options.iter().map(|o| normalize(o)).collect();
+            assert_eq!(distinct.len(), OPTIONS);
+        }
+    }
+
+    #[test]
+    fn stricter_graders_allow_closer_distractors() {
+        let exact = Grader { threshold: 0.0 };
+        let mut seen = BTreeSet::new();
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let options = options(ANSWER, CANDIDATES, &exact, &mut rng).unwrap();
+            assert_eq!(options.iter().filter(|o| o.as_str() == ANSWER).count(), 1);
+            seen.extend(options);
+        }
+        assert!(seen.contains("fotosynthesis"));
+        assert!(!seen.contains("Photosynthesis"));
+    }
+
+    #[test]
+    fn too_few_candidates() {
+        let grader = Grader::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        let accepted = ["Photosynthesis", "photosynthesis!", "", "fotosynthesis"];
+        assert_eq!(options(ANSWER, &accepted, &grader, &mut rng), None);
+        assert_eq!(options(ANSWER, &[], &grader, &mut rng), None);
+        let mut two = options(ANSWER, &["osmosis", "Osmosis"], &grader, &mut rng).unwrap();
+        two.sort();
+        assert_eq!(two, ["osmosis", "photosynthesis"]);
+    }
+
+    #[test]
+    fn distractors_of_the_same_kind_are_closer() {
+        assert!(distance("1969", "1945") < distance("1969", "Paris"));
+        assert!(distance("Paris", "Berlin") < distance("Paris", "the capital of France"));
+        assert_eq!(distance("", ""), 0.0);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    deck::Card, export::ExportFormat, first_line, import::ImportOptions, stats::History, today,
+    topics, FlashCardEngine, StudyDirection, StudyOrder,
+};
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Directory for topics and saved sessions [env: FLASHCARDS_DATA_DIR]
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+    /// Directory holding one sub-directory per topic [default: <data-dir>/topics]
+    #[arg(long, global = true)]
+    pub topics_dir: Option<PathBuf>,
+    /// Copy the topics of an earlier version (e.g. ./topics) into an empty topics directory
+    #[arg(long, global = true, value_name = "DIR")]
+    pub migrate_from: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open the study screen of a topic directly
+    Study(StudyArgs),
+    #[command(flatten)]
+    Deck(DeckCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct StudyArgs {
+    pub topic: String,
+    /// Skip the order prompt and start right away
+    #[arg(long, value_enum)]
+    pub order: Option<StudyOrder>,
+    /// Show the answers and ask for the questions, or mix both ways
+    #[arg(long, value_enum)]
+    pub direction: Option<StudyDirection>,
+    /// Pick each answer from numbered options instead of typing it
+    #[arg(long)]
+    pub choices: bool,
+}
+
+// Commands that run without the TUI.
+#[derive(Debug, Subcommand)]
+pub enum DeckCommand {
+    /// List topics, or the cards of one topic
+    List { topic: Option<String> },
+    /// Add a card to a topic, creating the topic if needed
+    Add {
+        topic: String,
+        #[arg(short, long)]
+        question: String,
+        #[arg(short, long)]
+        answer: String,
+        /// Tag to attach; may be repeated
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        #[arg(long, default_value = "")]
+        notes: String,
+    },
+    /// Remove a card, given its number in `list <topic>` or its id
+    Remove { topic: String, card: String },
+    /// Print a card with its schedule and statistics
+    Show { topic: String, card: String },
+    /// Import cards from a CSV/TSV file or an Anki .apkg package into a topic
+    Import {
+        /// CSV, TSV or .apkg file to read
+        file: PathBuf,
+        /// Topic to add the cards to; defaults to the file name
+        #[arg(long)]
+        topic: Option<String>,
+        #[command(flatten)]
+        opts: ImportOptions,
+    },
+    /// Export a topic to CSV, Anki-importable TSV, Markdown or JSON
+    Export {
+        topic: String,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        /// File to write, or - for stdout; defaults to <topic>.<extension>
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Summarize progress for all topics, or per card for one topic
+    Stats { topic: Option<String> },
+}
+
+// Topics are named by title or directory, see topics::find.
+fn open_topic(topics_dir: &Path, topic: &str) -> Result<FlashCardEngine> {
+    let topic = topics::find(topics_dir, topic)?;
+    FlashCardEngine::from_dir(&topics_dir.join(topic.dir))
+}
+
+fn find_card(eng: &FlashCardEngine, card: &str) -> Result<usize> {
+    if let Ok(n) = card.parse::<usize>() {
+        if (1..=eng.cards.len()).contains(&n) {
+            return Ok(n - 1);
+        }
+    }
+    eng.cards
+        .iter()
+        .position(|c| c.id == card)
+        .ok_or_else(|| anyhow::anyhow!("No card {card:?}"))
+}
+
+// Output is often piped into head/grep, so a closed pipe is not an error.
+pub fn run(command: DeckCommand, topics_dir: &Path) -> Result<()> {
+    match execute(command, topics_dir) {
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+// Runs `f` for every topic; a topic that cannot be read is reported and
+// skipped, and the command fails once all others are done.
+fn each_topic(
+    topics_dir: &Path,
+    mut f: impl FnMut(&[topics::Topic], &topics::Topic) -> Result<()>,
+) -> Result<()> {
+    let all = topics::list(topics_dir)?;
+    let mut failed = 0;
+    for topic in &all {
+        if let Err(e) = f(&all, topic) {
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+            {
+                return Err(e);
+            }
+            eprintln!("{}: {e:#}", topics::title_path(&all, topic));
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} topic(s) could not be read");
+    }
+    Ok(())
+}
+
+fn execute(command: DeckCommand, topics_dir: &Path) -> Result<()> {
+    let mut out = io::stdout().lock();
+    match command {
+        DeckCommand::List { topic: None } => each_topic(topics_dir, |all, topic| {
+            let eng = FlashCardEngine::from_dir(&topics_dir.join(&topic.dir))?;
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                topics::title_path(all, topic),
+                eng.cards.len(),
+                eng.due_count()
+            )?;
+            Ok(())
+        })?,
+        DeckCommand::List { topic: Some(topic) } => {
+            let eng = open_topic(topics_dir, &topic)?;
+            for (i, c) in eng.cards.iter().enumerate() {
+                writeln!(out, "{}\t{}\t{}", i + 1, c.id, first_line(&c.question))?;
+            }
+        }
+        DeckCommand::Add {
+            topic,
+            question,
+            answer,
+            tags,
+            notes,
+        } => {
+            let topic = topics::find_or_create(topics_dir, &topic)?;
+            let mut eng = FlashCardEngine::edit_dir(&topics_dir.join(topic.dir))?;
+            let mut card = Card::new(question, answer);
+            card.tags = tags;
+            card.notes = notes;
+            let id = card.id.clone();
+            eng.append_cards(vec![card]);
+            eng.persist_edits()?;
+            writeln!(out, "{id}")?;
+        }
+        DeckCommand::Remove { topic, card } => {
+            let topic = topics::find(topics_dir, &topic)?;
+            let mut eng = FlashCardEngine::edit_dir(&topics_dir.join(topic.dir))?;
+            let idx = find_card(&eng, &card)?;
+            let removed = eng.cards[idx].id.clone();
+            eng.remove_card(idx);
+            eng.persist_edits()?;
+            eprintln!("Removed card {removed}");
+        }
+        DeckCommand::Show { topic, card } => {
+            let eng = open_topic(topics_dir, &topic)?;
+            let idx = find_card(&eng, &card)?;
+            let c = &eng.cards[idx];
+            let s = &eng.schedule[idx];
+            writeln!(out, "Card {} ({})", idx + 1, c.id)?;
+            writeln!(out, "\nQuestion:\n{}", c.question)?;
+            writeln!(out, "\nAnswer:\n{}", c.answer)?;
+            if !c.tags.is_empty() {
+                writeln!(out, "\nTags: {}", c.tags.join(", "))?;
+            }
+            if !c.notes.is_empty() {
+                writeln!(out, "\nNotes:\n{}", c.notes)?;
+            }
+            writeln!(
+                out,
+                "\nDue {} • interval {} day(s) • ease {:.2}",
+                s.due, s.interval, s.ease
+            )?;
+            if let Some(st) = eng.stats.get(&c.id) {
+                writeln!(
+                    out,
+                    "Seen {} time(s) • {:.0}% correct • streak {}",
+                    st.seen,
+                    st.accuracy() * 100.0,
+                    st.streak
+                )?;
+            }
+            if let Some(st) = eng.stats.get_reverse(&c.id) {
+                writeln!(
+                    out,
+                    "Reversed: seen {} time(s) • {:.0}% correct • streak {}",
+                    st.seen,
+                    st.accuracy() * 100.0,
+                    st.streak
+                )?;
+            }
+        }
+        DeckCommand::Import { file, topic, opts } => {
+            let topic = match topic {
+                Some(t) => t,
+                None => file
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "Imported".into()),
+            };
+            let report = crate::import::import_file(&file, &opts)?;
+            let topic = topics::find_or_create(topics_dir, &topic)?;
+            let mut eng = FlashCardEngine::edit_dir(&topics_dir.join(&topic.dir))?;
+            let imported = report.cards.len();
+            eng.append_cards(report.cards);
+            eng.persist_edits()?;
+            writeln!(
+                out,
+                "Imported {imported} card(s) into topic {:?} ({} skipped)",
+                topic.title,
+                report.skipped.len()
+            )?;
+            for reason in &report.skipped {
+                writeln!(out, "  skipped {reason}")?;
+            }
+        }
+        DeckCommand::Export {
+            topic,
+            format,
+            output,
+        } => {
+            let topic = topics::find(topics_dir, &topic)?;
+            let eng = FlashCardEngine::from_dir(&topics_dir.join(&topic.dir))?;
+            let output = output.unwrap_or_else(|| {
+                PathBuf::from(format!(
+                    "{}.{}",
+                    topic.dir.replace('/', "-"),
+                    format.extension()
+                ))
+            });
+            if output.as_os_str() == "-" {
+                let text = crate::export::render(&eng.cards, format)?;
+                out.write_all(text.as_bytes())?;
+            } else {
+                crate::export::export(&eng.cards, format, &output)?;
+                eprintln!(
+                    "Exported {} card(s) to {}",
+                    eng.cards.len(),
+                    output.display()
+                );
+            }
+        }
+        DeckCommand::Stats { topic: None } => {
+            writeln!(out, "topic\tcards\tdue\tmature\tsessions\taccuracy")?;
+            each_topic(topics_dir, |all, topic| {
+                let eng = FlashCardEngine::from_dir(&topics_dir.join(&topic.dir))?;
+                let history = History::load(&eng.history_file())?;
+                let (cards, correct) = history
+                    .sessions
+                    .iter()
+                    .fold((0, 0), |(n, c), s| (n + s.cards, c + s.correct));
+                let accuracy = if cards == 0 {
+                    "-".to_string()
+                } else {
+                    format!("{:.0}%", correct as f64 * 100.0 / cards as f64)
+                };
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{accuracy}",
+                    topics::title_path(all, topic),
+                    eng.cards.len(),
+                    eng.due_count(),
+                    eng.schedule.iter().filter(|s| s.is_mature()).count(),
+                    history.sessions.len(),
+                )?;
+                Ok(())
+            })?;
+        }
+        DeckCommand::Stats { topic: Some(topic) } => {
+            let eng = open_topic(topics_dir, &topic)?;
+            let today = today();
+            writeln!(out, "#\tseen\taccuracy\tstreak\tdue\tquestion")?;
+            for (i, c) in eng.cards.iter().enumerate() {
+                let st = eng.stats.get(&c.id).cloned().unwrap_or_default();
+                let due = &eng.schedule[i].due;
+                let due = if *due <= today {
+                    "now".to_string()
+                } else {
+                    due.to_string()
+                };
+                let accuracy = if st.seen == 0 {
+                    "-".to_string()
+                } else {
+                    format!("{:.0}%", st.accuracy() * 100.0)
+                };
+                writeln!(
+                    out,
+                    "{}\t{}\t{accuracy}\t{}\t{due}\t{}",
+                    i + 1,
+                    st.seen,
+                    st.streak,
+                    first_line(&c.question)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use crate::{keymap::Keymap, topics::copy_dir, StudyDirection, StudyOrder};
+use anyhow::{Context, Result};
+use ratatui::style::Color;
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    env, fs,
+    num::{NonZeroU64, NonZeroUsize},
+    path::{Path, PathBuf},
+};
+
+const APP_DIR: &str = "flashcards-rs";
+const CONFIG_FILE: &str = "config.toml";
+pub const DATA_DIR_ENV: &str = "FLASHCARDS_DATA_DIR";
+// Where topics lived before the data directory existed, relative to the cwd.
+// Only used to suggest --migrate-from.
+const LEGACY_TOPICS: &str = "topics";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub data_dir: Option<PathBuf>,
+    // Where the card editor writes exports; defaults to exports/ in the data dir.
+    pub export_dir: Option<PathBuf>,
+    // Order used by Enter on the mode screen and by `study` without --order.
+    pub default_order: Option<StudyOrder>,
+    // Direction preselected on the mode screen and used by `study`.
+    pub default_direction: Option<StudyDirection>,
+    // Pick answers from numbered options instead of typing them.
+    pub multiple_choice: bool,
+    // Maximum number of cards per study session.
+    pub session_size: Option<NonZeroUsize>,
+    pub strictness: Strictness,
+    // Seconds after an unsaved card change before the deck is saved on its own.
+    pub autosave: Option<NonZeroU64>,
+    // j/k/gg/G in lists and normal/insert modes in the text fields.
+    pub vim: bool,
+    pub theme: Theme,
+    pub keymap: Keymap,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join(APP_DIR).join(CONFIG_FILE))
+    }
+
+    // Errors name the file and line so they fit on the hint bar.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|p| p.exists()) else {
+            return Ok(Self::default());
+        };
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Opening {}", path.display()))?;
+        toml::from_str(&text).map_err(|e| {
+            let line = e
+                .span()
+                .map(|s| text[..s.start].matches('\n').count() + 1)
+                .unwrap_or(1);
+            anyhow::anyhow!("{} line {line}: {}", path.display(), e.message().trim())
+        })
+    }
+}
+
+// How far a typed answer may be from the real one and still count as close:
+// one of the named levels or the allowed typos as a fraction of its length.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Strictness {
+    Exact,
+    Strict,
+    #[default]
+    Normal,
+    Lenient,
+    Threshold(f64),
+}
+
+impl<'de> Deserialize<'de> for Strictness {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Name(String),
+            Threshold(f64),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Threshold(t) if (0.0..=1.0).contains(&t) => Ok(Strictness::Threshold(t)),
+            Raw::Threshold(t) => Err(de::Error::custom(format!(
+                "strictness {t} is not between 0 and 1"
+            ))),
+            Raw::Name(name) => match name.as_str() {
+                "exact" => Ok(Strictness::Exact),
+                "strict" => Ok(Strictness::Strict),
+                "normal" => Ok(Strictness::Normal),
+                "lenient" => Ok(Strictness::Lenient),
+                _ => Err(de::Error::unknown_variant(
+                    &name,
+                    &["exact", "strict", "normal", "lenient"],
+                )),
+            },
+        }
+    }
+}
+
+impl Strictness {
+    pub fn threshold(self) -> f64 {
+        match self {
+            Strictness::Exact => 0.0,
+            Strictness::Strict => 0.1,
+            Strictness::Normal => 0.2,
+            Strictness::Lenient => 0.34,
+            Strictness::Threshold(t) => t,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub title: Color,
+    pub selected: Color,
+    pub accent: Color,
+    pub hint: Color,
+    pub notice: Color,
+    pub correct: Color,
+    pub close: Color,
+    pub wrong: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            title: Color::Yellow,
+            selected: Color::Yellow,
+            accent: Color::Cyan,
+            hint: Color::DarkGray,
+            notice: Color::Yellow,
+            correct: Color::Green,
+            close: Color::Yellow,
+            wrong: Color::Red,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub topics_dir: PathBuf,
+    pub sessions_dir: PathBuf,
+    pub trash_dir: PathBuf,
+    pub exports_dir: PathBuf,
+}
+
+// The data directory comes from --data-dir, then $FLASHCARDS_DATA_DIR, then
+// data_dir in config.toml, then the platform data dir (e.g.
+// $XDG_DATA_HOME/flashcards-rs). --topics-dir overrides only the topics root.
+pub fn resolve(
+    data_dir: Option<PathBuf>,
+    topics_dir: Option<PathBuf>,
+    migrate_from: Option<&Path>,
+    config: &Config,
+) -> Result<Paths> {
+    let data_dir = match data_dir
+        .or_else(|| {
+            env::var_os(DATA_DIR_ENV)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        })
+        .or_else(|| config.data_dir.as_deref().map(expand_home))
+    {
+        Some(d) => d,
+        None => dirs::data_dir()
+            .context("Cannot determine a data directory; pass --data-dir")?
+            .join(APP_DIR),
+    };
+    let paths = Paths {
+        topics_dir: topics_dir.unwrap_or_else(|| data_dir.join("topics")),
+        sessions_dir: data_dir.join("sessions"),
+        trash_dir: data_dir.join("trash"),
+        exports_dir: match &config.export_dir {
+            Some(dir) => expand_home(dir),
+            None => data_dir.join("exports"),
+        },
+    };
+    if let Some(from) = migrate_from {
+        migrate_topics(from, &paths.topics_dir)?;
+    }
+    Ok(paths)
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn has_entries(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|mut d| d.next().is_some())
+}
+
+// Earlier versions kept topics in ./topics. When there are no topics yet and
+// ./topics exists, point at --migrate-from rather than copying whatever the
+// current directory holds.
+pub fn legacy_hint(paths: &Paths) -> Option<String> {
+    (!has_entries(&paths.topics_dir) && Path::new(LEGACY_TOPICS).is_dir()).then(|| {
+        format!(
+            "Found ./topics from an earlier version; run with --migrate-from {LEGACY_TOPICS} \
+             to copy it to {}",
+            paths.topics_dir.display()
+        )
+    })
+}
+
+// Copies the topics of an earlier version into a new topics directory; the
+// old directory is left in place.
+fn migrate_topics(legacy: &Path, topics_dir: &Path) -> Result<()> {
+    if !legacy.is_dir() {
+        anyhow::bail!("{} is not a directory", legacy.display());
+    }
+    if has_entries(topics_dir) {
+        anyhow::bail!(
+            "{} already has topics; not copying {}",
+            topics_dir.display(),
+            legacy.display()
+        );
+    }
+    // Copy into a sibling first so an interrupted copy is retried next time.
+    let partial = topics_dir.with_extension("partial");
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    copy_dir(legacy, &partial)
+        .with_context(|| format!("Copying {} to {}", legacy.display(), topics_dir.display()))?;
+    if topics_dir.exists() {
+        fs::remove_dir(topics_dir)?;
+    }
+    fs::rename(&partial, topics_dir)?;
+    eprintln!(
+        "Copied {} to {}; the old directory is no longer used",
+        legacy.display(),
+        topics_dir.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fs, path::Path};
+
+pub const DECK_FILE: &str = "deck.toml";
+const LEGACY_QUESTIONS: &str = "questions.txt";
+const LEGACY_ANSWERS: &str = "answers.txt";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Card {
+    pub id: String,
+    pub question: String,
+    pub answer: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+}
+
+impl Card {
+    pub fn new(question: String, answer: String) -> Self {
+        Self {
+            id: new_id(),
+            question,
+            answer,
+            tags: Vec::new(),
+            notes: String::new(),
+        }
+    }
+}
+
+// Splits tags separated by spaces or commas, dropping duplicates.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(|c: char| c.is_whitespace() || c == ',') {
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+// Cards to study or list: those with at least one included tag (any card if
+// none are included) and none of the excluded ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    pub include: BTreeSet<String>,
+    pub exclude: BTreeSet<String>,
+}
+
+impl TagFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, card: &Card) -> bool {
+        (self.include.is_empty() || card.tags.iter().any(|t| self.include.contains(t)))
+            && !card.tags.iter().any(|t| self.exclude.contains(t))
+    }
+
+    // Include, exclude or neither: pressing the same key again clears it.
+    pub fn toggle(&mut self, tag: &str, include: bool) {
+        let (on, off) = if include {
+            (&mut self.include, &mut self.exclude)
+        } else {
+            (&mut self.exclude, &mut self.include)
+        };
+        off.remove(tag);
+        if !on.remove(tag) {
+            on.insert(tag.to_string());
+        }
+    }
+
+    // e.g. "+verbs -hard"
+    pub fn summary(&self) -> String {
+        self.include
+            .iter()
+            .map(|t| format!("+{t}"))
+            .chain(self.exclude.iter().map(|t| format!("-{t}")))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DeckFile {
+    #[serde(default, rename = "card")]
+    cards: Vec<Card>,
+}
+
+pub fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+// Loads <dir>/deck.toml, or reads a legacy questions.txt/answers.txt pair
+// without changing anything on disk; see migrate.
+pub fn load(dir: &Path) -> Result<Vec<Card>> {
+    let path = dir.join(DECK_FILE);
+    if path.exists() {
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Opening {}", path.display()))?;
+        let deck: DeckFile =
+            toml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))?;
+        return Ok(deck.cards);
+    }
+    let q = dir.join(LEGACY_QUESTIONS);
+    let a = dir.join(LEGACY_ANSWERS);
+    if !q.exists() && !a.exists() {
+        return Ok(Vec::new());
+    }
+    read_legacy(&q, &a)
+}
+
+// Converts a legacy questions.txt/answers.txt pair to <dir>/deck.toml. Only
+// done when a topic is opened for study or editing, so that listing or
+// searching never writes. The legacy files are kept as *.bak next to the new
+// deck.
+pub fn migrate(dir: &Path) -> Result<()> {
+    let path = dir.join(DECK_FILE);
+    let q = dir.join(LEGACY_QUESTIONS);
+    let a = dir.join(LEGACY_ANSWERS);
+    if path.exists() || (!q.exists() && !a.exists()) {
+        return Ok(());
+    }
+    save(&path, &read_legacy(&q, &a)?)?;
+    for legacy in [q, a] {
+        if legacy.exists() {
+            fs::rename(&legacy, legacy.with_extension("txt.bak"))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_legacy(q: &Path, a: &Path) -> Result<Vec<Card>> {
+    let read = |p: &Path| {
+        if p.exists() {
+            crate::read_nonempty_lines(p)
+        } else {
+            Ok(Vec::new())
+        }
+    };
+    let questions = read(q)?;
+    let answers = read(a)?;
+    if questions.len() != answers.len() {
+        anyhow::bail!(
+            "Cannot migrate {}: {} questions vs {} answers",
+            q.parent().unwrap_or(q).display(),
+            questions.len(),
+            answers.len()
+        );
+    }
+    Ok(questions
+        .into_iter()
+        .zip(answers)
+        .map(|(q, a)| Card::new(q, a))
+        .collect())
+}
+
+pub fn save(path: &Path, cards: &[Card]) -> Result<()> {
+    let deck = DeckFile {
+        cards: cards.to_vec(),
+    };
+    crate::write_atomic(path, &toml::to_string_pretty(&deck)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "flashcards-deck-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(LEGACY_QUESTIONS), "Q1\n\nQ2\n").unwrap();
+        fs::write(dir.join(LEGACY_ANSWERS), "A1\nA2\n").unwrap();
+        dir
+    }
+
+    fn files(dir: &Path) -> BTreeSet<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn load_reads_legacy_decks_without_writing() {
+        let dir = legacy_dir("load");
+        let before = files(&dir);
+        let cards = load(&dir).unwrap();
+        let pairs: Vec<_> = cards
+            .iter()
+            .map(|c| (c.question.as_str(), c.answer.as_str()))
+            .collect();
+        assert_eq!(pairs, [("Q1", "A1"), ("Q2", "A2")]);
+        assert_eq!(files(&dir), before);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_writes_the_deck_and_keeps_backups() {
+        let dir = legacy_dir("migrate");
+        migrate(&dir).unwrap();
+        assert_eq!(
+            files(&dir),
+            BTreeSet::from(["answers.txt.bak", "deck.toml", "questions.txt.bak"].map(String::from))
+        );
+        let cards = load(&dir).unwrap();
+        assert_eq!(cards.len(), 2);
+        migrate(&dir).unwrap();
+        assert_eq!(load(&dir).unwrap(), cards);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use crate::deck::Card;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    /// Tab-separated text that Anki's "Import File" understands
+    Tsv,
+    Markdown,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Tsv => "Anki TSV",
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Json => "JSON",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Tsv => "txt",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+pub fn render(cards: &[Card], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Csv => to_csv(cards),
+        ExportFormat::Tsv => Ok(to_anki_tsv(cards)),
+        ExportFormat::Markdown => Ok(to_markdown(cards)),
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(cards)? + "\n"),
+    }
+}
+
+pub fn export(cards: &[Card], format: ExportFormat, path: &Path) -> Result<()> {
+    crate::write_atomic(path, &render(cards, format)?)
+}
+
+fn to_csv(cards: &[Card]) -> Result<String> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    w.write_record(["question", "answer", "tags", "notes"])?;
+    for c in cards {
+        w.write_record([&c.question, &c.answer, &c.tags.join(" "), &c.notes])?;
+    }
+    Ok(String::from_utf8(w.into_inner()?)?)
+}
+
+fn to_anki_tsv(cards: &[Card]) -> String {
+    let field = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('\t', "    ")
+            .replace('\n', "<br>")
+    };
+    let mut out = String::from("#separator:tab\n#html:true\n#tags column:3\n");
+    for c in cards {
+        out.push_str(&format!(
+            "{}\t{}\t{}\n",
+            field(&c.question),
+            field(&c.answer),
+            c.tags.join(" ")
+        ));
+    }
+    out
+}
+
+fn to_markdown(cards: &[Card]) -> String {
+    let cell = |s: &str| s.replace('|', "\\|").replace('\n', "<br>");
+    let mut out =
+        String::from("| # | Question | Answer | Tags |\n|---|----------|--------|------|\n");
+    for (i, c) in cards.iter().enumerate() {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            i + 1,
+            cell(&c.question),
+            cell(&c.answer),
+            cell(&c.tags.join(", "))
+        ));
+    }
+    out
+}
@@ -0,0 +1,155 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grade {
+    Correct,
+    Close,
+    Wrong,
+}
+
+impl Grade {
+    pub fn label(self) -> &'static str {
+        match self {
+            Grade::Correct => "Correct",
+            Grade::Close => "Close",
+            Grade::Wrong => "Wrong",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Grader {
+    // Largest edit distance still counted as close, as a fraction of the
+    // normalized answer length.
+    pub threshold: f64,
+}
+
+impl Default for Grader {
+    fn default() -> Self {
+        Self { threshold: 0.2 }
+    }
+}
+
+impl Grader {
+    pub fn grade(&self, response: &str, answer: &str) -> Grade {
+        let resp = normalize(response);
+        let ans = normalize(answer);
+        if resp.is_empty() {
+            return Grade::Wrong;
+        }
+        if resp == ans {
+            return Grade::Correct;
+        }
+        let allowed = (ans.chars().count() as f64 * self.threshold).floor() as usize;
+        if levenshtein(&resp, &ans) <= allowed {
+            Grade::Close
+        } else {
+            Grade::Wrong
+        }
+    }
+}
+
+// Lowercases, folds typographic quotes and dashes, drops punctuation and
+// collapses whitespace so that "Earth’s crust." matches "earths crust".
+pub fn normalize(s: &str) -> String {
+    let folded: String = s
+        .chars()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => '"',
+            '\u{2010}'..='\u{2015}' => '-',
+            c => c,
+        })
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == '-' || c == '/' { ' ' } else { c })
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_folds_case_quotes_and_punctuation() {
+        assert_eq!(
+            normalize("Earth\u{2019}s  Crust."),
+            normalize("earth's crust")
+        );
+        assert_eq!(normalize("Earth\u{2019}s crust."), "earths crust");
+        assert_eq!(
+            normalize("  well\u{2013}known / x-ray "),
+            "well known x ray"
+        );
+        assert_eq!(normalize("?!"), "");
+        assert_eq!(normalize("Ärger"), "ärger");
+    }
+
+    #[test]
+    fn levenshtein_counts_characters() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+        assert_eq!(levenshtein("café", "cafe"), 1);
+        assert_eq!(levenshtein("日本語", "日本"), 1);
+    }
+
+    #[test]
+    fn grade_at_threshold() {
+        // 14 characters at 0.2 allow two edits.
+        let grader = Grader::default();
+        assert_eq!(
+            grader.grade("Photosynthesis.", "photosynthesis"),
+            Grade::Correct
+        );
+        assert_eq!(
+            grader.grade("photosynthesys", "photosynthesis"),
+            Grade::Close
+        );
+        assert_eq!(
+            grader.grade("fotosynthesis", "photosynthesis"),
+            Grade::Close
+        );
+        assert_eq!(
+            grader.grade("fotosynthesys", "photosynthesis"),
+            Grade::Wrong
+        );
+        assert_eq!(grader.grade("", "photosynthesis"), Grade::Wrong);
+        assert_eq!(grader.grade("...", "photosynthesis"), Grade::Wrong);
+    }
+
+    #[test]
+    fn zero_threshold_needs_exact_match() {
+        let grader = Grader { threshold: 0.0 };
+        assert_eq!(
+            grader.grade("photosynthesis", "Photosynthesis!"),
+            Grade::Correct
+        );
+        assert_eq!(
+            grader.grade("photosynthesys", "photosynthesis"),
+            Grade::Wrong
+        );
+    }
+
+    #[test]
+    fn short_answers_allow_no_typos() {
+        // Four characters at 0.2 round down to zero edits.
+        let grader = Grader::default();
+        assert_eq!(grader.grade("1912", "1913"), Grade::Wrong);
+    }
+}
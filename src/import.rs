@@ -0,0 +1,660 @@
+use crate::deck::{self, Card};
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Csv,
+    Tsv,
+    Apkg,
+}
+
+// A column given either as a 1-based position or as a header name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("columns are numbered from 1".into()),
+            Ok(n) => Ok(Column::Index(n - 1)),
+            Err(_) if s.is_empty() => Err("empty column name".into()),
+            Err(_) => Ok(Column::Name(s.to_string())),
+        }
+    }
+}
+
+impl Column {
+    // `names` are the header of a CSV/TSV file or the fields of an Anki note.
+    fn resolve(&self, names: Option<&[String]>) -> Result<usize> {
+        match (self, names) {
+            (Column::Index(i), _) => Ok(*i),
+            (Column::Name(name), Some(names)) => names
+                .iter()
+                .position(|c| c.trim().eq_ignore_ascii_case(name))
+                .with_context(|| format!("No column or field named {name:?}")),
+            (Column::Name(name), None) => {
+                anyhow::bail!("No column named {name:?}; pass --header to name columns")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    question: usize,
+    answer: usize,
+    tags: Option<usize>,
+    notes: Option<usize>,
+}
+
+impl Columns {
+    fn resolve(opts: &ImportOptions, names: Option<&[String]>) -> Result<Self> {
+        let optional = |c: &Option<Column>| c.as_ref().map(|c| c.resolve(names)).transpose();
+        Ok(Self {
+            question: opts.question_col.resolve(names)?,
+            answer: opts.answer_col.resolve(names)?,
+            tags: optional(&opts.tags_col)?,
+            notes: optional(&opts.notes_col)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ImportOptions {
+    /// Input format; guessed from the file extension when omitted
+    #[arg(long, value_enum)]
+    pub format: Option<ImportFormat>,
+    /// Field delimiter for CSV/TSV input
+    #[arg(long)]
+    pub delimiter: Option<char>,
+    /// Treat the first row as column names
+    #[arg(long)]
+    pub header: bool,
+    /// Question column or Anki field (1-based position, header name or Anki field name)
+    #[arg(long, default_value = "1")]
+    pub question_col: Column,
+    /// Answer column or Anki field (1-based position, header name or Anki field name)
+    #[arg(long, default_value = "2")]
+    pub answer_col: Column,
+    /// Column holding space- or comma-separated tags
+    #[arg(long)]
+    pub tags_col: Option<Column>,
+    /// Column holding notes
+    #[arg(long)]
+    pub notes_col: Option<Column>,
+}
+
+struct Record {
+    label: String,
+    fields: Vec<String>,
+    // Tags carried by the source itself (Anki notes), used when no tags
+    // column is mapped.
+    tags: Option<String>,
+    // Field names of the note type of an Anki note.
+    names: Option<Vec<String>>,
+}
+
+pub struct ImportReport {
+    pub cards: Vec<Card>,
+    pub skipped: Vec<String>,
+}
+
+pub fn import_file(path: &Path, opts: &ImportOptions) -> Result<ImportReport> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let format = match (opts.format, ext.as_str()) {
+        (Some(f), _) => f,
+        (None, "apkg" | "colpkg") => ImportFormat::Apkg,
+        (None, "tsv" | "tab" | "txt") => ImportFormat::Tsv,
+        (None, _) => ImportFormat::Csv,
+    };
+    match format {
+        ImportFormat::Apkg => import_apkg(path, opts),
+        ImportFormat::Csv | ImportFormat::Tsv => {
+            let default = if format == ImportFormat::Tsv {
+                '\t'
+            } else {
+                ','
+            };
+            let header = anki_text_header(path)?;
+            let delimiter = opts.delimiter.or(header.separator).unwrap_or(default);
+            if !delimiter.is_ascii() {
+                anyhow::bail!("Delimiter must be a single ASCII character");
+            }
+            import_delimited(path, delimiter as u8, &header, opts)
+        }
+    }
+}
+
+// Header lines Anki writes at the top of its text exports.
+const ANKI_HEADERS: &[&str] = &[
+    "separator",
+    "html",
+    "tags column",
+    "columns",
+    "notetype",
+    "notetype column",
+    "deck",
+    "deck column",
+    "guid column",
+];
+
+struct AnkiTextHeader {
+    lines: u64,
+    separator: Option<char>,
+    // "#html:false" marks fields as plain text, so "<" is kept as is.
+    html: bool,
+}
+
+// Anki's text exports start with lines such as "#separator:tab" and
+// "#html:true". Any other line, even one starting with '#', is a card.
+fn anki_text_header(path: &Path) -> Result<AnkiTextHeader> {
+    let bytes = fs::read(path).with_context(|| format!("Opening {}", path.display()))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let mut header = AnkiTextHeader {
+        lines: 0,
+        separator: None,
+        html: true,
+    };
+    for line in text.lines() {
+        let Some((key, value)) = line.strip_prefix('#').and_then(|l| l.split_once(':')) else {
+            break;
+        };
+        if !ANKI_HEADERS.contains(&key) {
+            break;
+        }
+        if key == "separator" {
+            header.separator = match value.trim().to_lowercase().as_str() {
+                "tab" => Some('\t'),
+                "comma" => Some(','),
+                "semicolon" => Some(';'),
+                "space" => Some(' '),
+                "pipe" => Some('|'),
+                "colon" => Some(':'),
+                v if v.chars().count() == 1 => v.chars().next(),
+                _ => None,
+            };
+        }
+        if key == "html" {
+            header.html = value.trim() != "false";
+        }
+        header.lines += 1;
+    }
+    Ok(header)
+}
+
+fn import_delimited(
+    path: &Path,
+    delimiter: u8,
+    anki: &AnkiTextHeader,
+    opts: &ImportOptions,
+) -> Result<ImportReport> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("Opening {}", path.display()))?;
+    let mut rows = Vec::new();
+    for rec in reader.records() {
+        let rec = rec.with_context(|| format!("Reading {}", path.display()))?;
+        let line = rec.position().map(|p| p.line()).unwrap_or(0);
+        if line <= anki.lines {
+            continue;
+        }
+        rows.push(Record {
+            label: format!("line {line}"),
+            fields: rec.iter().map(str::to_string).collect(),
+            tags: None,
+            names: None,
+        });
+    }
+    let header = if opts.header && !rows.is_empty() {
+        Some(rows.remove(0).fields)
+    } else {
+        None
+    };
+    build_cards(rows, header.as_deref(), anki.html, opts)
+}
+
+fn import_apkg(path: &Path, opts: &ImportOptions) -> Result<ImportReport> {
+    let file = File::open(path).with_context(|| format!("Opening {}", path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("{} is not an Anki package", path.display()))?;
+    let names: Vec<String> = archive
+        .file_names()
+        .map(|n| n.map(|n| n.into_owned()))
+        .collect::<zip::result::ZipResult<_>>()?;
+    let entry = ["collection.anki21", "collection.anki2"]
+        .into_iter()
+        .find(|n| names.iter().any(|x| x == n));
+    let Some(entry) = entry else {
+        if names.iter().any(|n| n == "collection.anki21b") {
+            anyhow::bail!(
+                "This package uses Anki's newer compressed format; re-export it with \
+                 \"Support older Anki versions\" enabled"
+            );
+        }
+        anyhow::bail!("No Anki collection found in {}", path.display());
+    };
+
+    let (tmp, mut dst) = TempFile::create()?;
+    let mut src = archive.by_name(entry)?;
+    io::copy(&mut src, &mut dst).with_context(|| format!("Writing {}", tmp.0.display()))?;
+    drop(dst);
+    let rows = read_anki_notes(&tmp.0)?;
+    build_cards(rows, None, true, opts)
+}
+
+// Scratch copy of the collection for SQLite. The name is random and the file
+// is created with create_new, so nothing already in the temp directory is
+// reused or followed. It is removed when dropped, on every return path.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn create() -> Result<(Self, File)> {
+        let dir = std::env::temp_dir();
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        for _ in 0..16 {
+            let path = dir.join(format!(
+                "flashcards-import-{}-{:016x}.anki2",
+                std::process::id(),
+                rand::random::<u64>()
+            ));
+            match options.open(&path) {
+                Ok(file) => return Ok((Self(path), file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).with_context(|| format!("Creating {}", path.display())),
+            }
+        }
+        anyhow::bail!("Could not create a temporary file in {}", dir.display())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn read_anki_notes(db: &Path) -> Result<Vec<Record>> {
+    let conn =
+        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let names = anki_field_names(&conn)?;
+    let mut stmt = conn.prepare("SELECT mid, flds, tags FROM notes ORDER BY id")?;
+    let rows = stmt
+        .query_map([], |row| {
+            let mid: i64 = row.get(0)?;
+            let flds: String = row.get(1)?;
+            let tags: String = row.get(2)?;
+            Ok((mid, flds, tags))
+        })?
+        .enumerate()
+        .map(|(i, r)| {
+            r.map(|(mid, flds, tags)| Record {
+                label: format!("note {}", i + 1),
+                fields: flds.split('\x1f').map(str::to_string).collect(),
+                tags: Some(tags),
+                names: names.get(&mid).cloned(),
+            })
+        })
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+// Field names of each note type, by note type id. Older collections keep the
+// note types as JSON in col.models, newer ones in the fields table.
+fn anki_field_names(conn: &rusqlite::Connection) -> Result<BTreeMap<i64, Vec<String>>> {
+    let mut names = BTreeMap::new();
+    let models: String = conn
+        .query_row("SELECT models FROM col", [], |row| row.get(0))
+        .unwrap_or_default();
+    if let Ok(serde_json::Value::Object(models)) = serde_json::from_str(&models) {
+        for (id, model) in models {
+            let (Ok(id), Some(fields)) = (id.parse::<i64>(), model["flds"].as_array()) else {
+                continue;
+            };
+            let mut fields: Vec<(i64, String)> = fields
+                .iter()
+                .filter_map(|f| Some((f["ord"].as_i64()?, f["name"].as_str()?.to_string())))
+                .collect();
+            fields.sort();
+            names.insert(id, fields.into_iter().map(|(_, name)| name).collect());
+        }
+    }
+    if names.is_empty() {
+        if let Ok(mut stmt) = conn.prepare("SELECT ntid, name FROM fields ORDER BY ntid, ord") {
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            for row in rows {
+                let (id, name): (i64, String) = row?;
+                names.entry(id).or_insert_with(Vec::new).push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+fn build_cards(
+    rows: Vec<Record>,
+    header: Option<&[String]>,
+    html: bool,
+    opts: &ImportOptions,
+) -> Result<ImportReport> {
+    // Anki notes name their own fields; columns of a file are resolved once.
+    let shared = if rows.iter().any(|r| r.names.is_some()) {
+        None
+    } else {
+        Some(Columns::resolve(opts, header)?)
+    };
+
+    let mut cards = Vec::new();
+    let mut skipped = Vec::new();
+    for rec in &rows {
+        let cols = match shared {
+            Some(cols) => cols,
+            None => match Columns::resolve(opts, rec.names.as_deref()) {
+                Ok(cols) => cols,
+                Err(e) => {
+                    skipped.push(format!("{}: {e:#}", rec.label));
+                    continue;
+                }
+            },
+        };
+        let field = |c: usize| {
+            rec.fields.get(c).map(|s| {
+                if html {
+                    strip_html(s)
+                } else {
+                    s.trim().to_string()
+                }
+            })
+        };
+        let (Some(question), Some(answer)) = (field(cols.question), field(cols.answer)) else {
+            skipped.push(format!("{}: only {} field(s)", rec.label, rec.fields.len()));
+            continue;
+        };
+        if question.is_empty() || answer.is_empty() {
+            skipped.push(format!("{}: empty question or answer", rec.label));
+            continue;
+        }
+        let mut card = Card::new(question, answer);
+        let tags = match cols.tags {
+            Some(c) => field(c),
+            None => rec.tags.clone(),
+        };
+        if let Some(tags) = tags {
+            card.tags = deck::parse_tags(&tags);
+        }
+        if let Some(notes) = cols.notes.and_then(field) {
+            card.notes = notes;
+        }
+        cards.push(card);
+    }
+    Ok(ImportReport { cards, skipped })
+}
+
+// Reduces Anki/spreadsheet HTML to plain text: block tags become newlines,
+// other tags and [sound:...] references are dropped and entities decoded.
+pub fn strip_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find(['<', '[']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with('<') {
+            let Some(end) = rest.find('>') else {
+                break;
+            };
+            let tag = rest[1..end].trim_start_matches('/').to_ascii_lowercase();
+            let name = tag
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or("");
+            if matches!(name, "br" | "div" | "p" | "li" | "tr") && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            rest = &rest[end + 1..];
+        } else if rest.starts_with("[sound:") {
+            match rest.find(']') {
+                Some(end) => rest = &rest[end + 1..],
+                None => break,
+            }
+        } else {
+            out.push('[');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    let decoded = decode_entities(&out);
+    decoded
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let name = &rest[1..end];
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" | "#39" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if name.starts_with("#x") || name.starts_with("#X") => {
+                    u32::from_str_radix(&name[2..], 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                }
+                _ if name.starts_with('#') => name[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts() -> ImportOptions {
+        ImportOptions {
+            format: None,
+            delimiter: None,
+            header: false,
+            question_col: Column::Index(0),
+            answer_col: Column::Index(1),
+            tags_col: None,
+            notes_col: None,
+        }
+    }
+
+    fn record(line: usize, fields: &[&str]) -> Record {
+        Record {
+            label: format!("line {line}"),
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            tags: None,
+            names: None,
+        }
+    }
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "flashcards-import-test-{}-{name}",
+            std::process::id()
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn strip_html_keeps_text_and_line_breaks() {
+        assert_eq!(strip_html("<b>bold</b> text"), "bold text");
+        assert_eq!(strip_html("one<br>two<br/>three"), "one\ntwo\nthree");
+        assert_eq!(strip_html("<div>a</div><div>b</div>"), "a\nb");
+        assert_eq!(strip_html("word [sound:x.mp3]"), "word");
+        assert_eq!(strip_html("[1] and [a]"), "[1] and [a]");
+        assert_eq!(strip_html("a &lt;b&gt; c"), "a <b> c");
+        assert_eq!(strip_html("unclosed <tag"), "unclosed <tag");
+    }
+
+    #[test]
+    fn decode_entities_handles_named_and_numeric() {
+        assert_eq!(decode_entities("&amp;&quot;&apos;&#39;"), "&\"''");
+        assert_eq!(decode_entities("&#233;t&#xE9;"), "été");
+        assert_eq!(decode_entities("a&nbsp;b"), "a b");
+        assert_eq!(decode_entities("fish & chips"), "fish & chips");
+        assert_eq!(decode_entities("&unknown;"), "&unknown;");
+        assert_eq!(decode_entities("&#xZZ;"), "&#xZZ;");
+    }
+
+    #[test]
+    fn build_cards_skips_and_reports_bad_rows() {
+        let rows = vec![
+            record(1, &["Q1", "A1"]),
+            record(2, &["only one"]),
+            record(3, &["Q3", "  "]),
+            record(4, &["<i>Q4</i>", "A4", "extra"]),
+        ];
+        let report = build_cards(rows, None, true, &opts()).unwrap();
+        let questions: Vec<_> = report.cards.iter().map(|c| c.question.as_str()).collect();
+        assert_eq!(questions, ["Q1", "Q4"]);
+        assert_eq!(
+            report.skipped,
+            [
+                "line 2: only 1 field(s)",
+                "line 3: empty question or answer"
+            ]
+        );
+    }
+
+    #[test]
+    fn build_cards_maps_named_columns() {
+        let header: Vec<String> = ["Tags", "Term", "Definition"].map(String::from).to_vec();
+        let opts = ImportOptions {
+            question_col: Column::Name("term".into()),
+            answer_col: Column::Name("Definition".into()),
+            tags_col: Some(Column::Name("tags".into())),
+            ..opts()
+        };
+        let rows = vec![record(2, &["a, b", "Q", "A"])];
+        let report = build_cards(rows, Some(&header), true, &opts).unwrap();
+        assert_eq!(report.cards[0].question, "Q");
+        assert_eq!(report.cards[0].answer, "A");
+        assert_eq!(report.cards[0].tags, ["a", "b"]);
+
+        let missing = ImportOptions {
+            question_col: Column::Name("Front".into()),
+            ..opts
+        };
+        assert!(build_cards(Vec::new(), Some(&header), true, &missing).is_err());
+        assert!(build_cards(Vec::new(), None, true, &missing).is_err());
+    }
+
+    #[test]
+    fn anki_header_lines_are_skipped_but_hash_rows_kept() {
+        let path = temp_file(
+            "header.txt",
+            "#separator:Semicolon\n#html:false\n#include <stdio.h>;Header for printf\nQ;A\n",
+        );
+        let report = import_file(&path, &opts()).unwrap();
+        fs::remove_file(&path).unwrap();
+        let cards: Vec<_> = report
+            .cards
+            .iter()
+            .map(|c| (c.question.as_str(), c.answer.as_str()))
+            .collect();
+        assert_eq!(
+            cards,
+            [("#include <stdio.h>", "Header for printf"), ("Q", "A")]
+        );
+        assert!(report.skipped.is_empty());
+    }
+
+    #[test]
+    fn anki_fields_can_be_named() {
+        let db = std::env::temp_dir().join(format!(
+            "flashcards-import-test-{}-notes.anki2",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&db);
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE col (models TEXT);
+               INSERT INTO col VALUES ('{"1": {"flds": [{"name": "Front", "ord": 0}, {"name": "Back", "ord": 1}]},
+                                         "2": {"flds": [{"name": "Text", "ord": 0}]}}');
+               CREATE TABLE notes (id INTEGER, mid INTEGER, flds TEXT, tags TEXT);
+               INSERT INTO notes VALUES (1, 1, 'hola' || char(31) || 'hello', ' greeting ');
+               INSERT INTO notes VALUES (2, 2, 'cloze', '');"#,
+        )
+        .unwrap();
+        drop(conn);
+        let rows = read_anki_notes(&db).unwrap();
+        fs::remove_file(&db).unwrap();
+        let opts = ImportOptions {
+            question_col: Column::Name("back".into()),
+            answer_col: Column::Name("Front".into()),
+            ..opts()
+        };
+        let report = build_cards(rows, None, true, &opts).unwrap();
+        assert_eq!(report.cards.len(), 1);
+        assert_eq!(report.cards[0].question, "hello");
+        assert_eq!(report.cards[0].answer, "hola");
+        assert_eq!(report.cards[0].tags, ["greeting"]);
+        assert_eq!(
+            report.skipped,
+            ["note 2: No column or field named \"back\""]
+        );
+    }
+
+    #[test]
+    fn temp_files_are_unique_and_removed_on_drop() {
+        let (a, file_a) = TempFile::create().unwrap();
+        let (b, file_b) = TempFile::create().unwrap();
+        assert_ne!(a.0, b.0);
+        let paths = [a.0.clone(), b.0.clone()];
+        assert!(paths.iter().all(|p| p.is_file()));
+        drop((a, file_a, b, file_b));
+        assert!(!paths.iter().any(|p| p.exists()));
+    }
+}
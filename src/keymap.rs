@@ -0,0 +1,789 @@
+use crate::{export::ExportFormat, srs::Rating, StudyDirection};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{de, Deserialize, Deserializer};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+// Something the user can do with a key press. Which actions are available
+// depends on the scope, roughly one per screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Help,
+    MoveUp,
+    MoveDown,
+    Open,
+    CreateTopic,
+    CreateSubtopic,
+    Search,
+    Expand,
+    Collapse,
+    RenameTopic,
+    DuplicateTopic,
+    DeleteTopic,
+    MergeTopic,
+    StartQuiz,
+    EditCards,
+    ShowStats,
+    Back,
+    EditQuestion,
+    EditAnswer,
+    AddCard,
+    DeleteCard,
+    MoveCardUp,
+    MoveCardDown,
+    EditTags,
+    FilterTags,
+    FindCard,
+    IncludeTag,
+    ExcludeTag,
+    ClearTags,
+    Undo,
+    Redo,
+    Save,
+    Export,
+    ExportCsv,
+    ExportTsv,
+    ExportMarkdown,
+    ExportJson,
+    SwitchView,
+    OrderRandom,
+    OrderSequential,
+    OrderDue,
+    OrderDefault,
+    DirectionForward,
+    DirectionReverse,
+    DirectionMixed,
+    ToggleChoices,
+    Choice1,
+    Choice2,
+    Choice3,
+    Choice4,
+    Submit,
+    Cancel,
+    Review,
+    NextCard,
+    RateAgain,
+    RateHard,
+    RateGood,
+    RateEasy,
+    SaveCard,
+    Discard,
+    Yes,
+    No,
+}
+
+impl Action {
+    pub fn rating(self) -> Option<Rating> {
+        match self {
+            Action::RateAgain => Some(Rating::Again),
+            Action::RateHard => Some(Rating::Hard),
+            Action::RateGood => Some(Rating::Good),
+            Action::RateEasy => Some(Rating::Easy),
+            _ => None,
+        }
+    }
+
+    pub fn export_format(self) -> Option<ExportFormat> {
+        match self {
+            Action::ExportCsv => Some(ExportFormat::Csv),
+            Action::ExportTsv => Some(ExportFormat::Tsv),
+            Action::ExportMarkdown => Some(ExportFormat::Markdown),
+            Action::ExportJson => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    // Index of the multiple-choice option the action picks.
+    pub fn choice(self) -> Option<usize> {
+        match self {
+            Action::Choice1 => Some(0),
+            Action::Choice2 => Some(1),
+            Action::Choice3 => Some(2),
+            Action::Choice4 => Some(3),
+            _ => None,
+        }
+    }
+
+    pub fn direction(self) -> Option<StudyDirection> {
+        match self {
+            Action::DirectionForward => Some(StudyDirection::Forward),
+            Action::DirectionReverse => Some(StudyDirection::Reverse),
+            Action::DirectionMixed => Some(StudyDirection::Mixed),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Help => "Help",
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::Open => "Open",
+            Action::CreateTopic => "Create topic",
+            Action::CreateSubtopic => "Create subtopic",
+            Action::Search => "Search all topics",
+            Action::Expand => "Expand",
+            Action::Collapse => "Collapse",
+            Action::RenameTopic => "Rename",
+            Action::DuplicateTopic => "Duplicate",
+            Action::DeleteTopic => "Delete",
+            Action::MergeTopic => "Merge into…",
+            Action::StartQuiz => "Start quiz",
+            Action::EditCards => "Edit cards",
+            Action::ShowStats => "Statistics",
+            Action::Back => "Back",
+            Action::EditQuestion => "Edit question",
+            Action::EditAnswer => "Edit answer",
+            Action::AddCard => "Add card",
+            Action::DeleteCard => "Delete card",
+            Action::MoveCardUp => "Move card up",
+            Action::MoveCardDown => "Move card down",
+            Action::EditTags => "Edit tags",
+            Action::FilterTags => "Filter by tag",
+            Action::FindCard => "Find",
+            Action::IncludeTag => "Include",
+            Action::ExcludeTag => "Exclude",
+            Action::ClearTags => "Clear filter",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Save => "Save to file",
+            Action::Export => "Export",
+            Action::ExportCsv => "CSV",
+            Action::ExportTsv => "Anki TSV",
+            Action::ExportMarkdown => "Markdown",
+            Action::ExportJson => "JSON",
+            Action::SwitchView => "Dashboard/cards",
+            Action::OrderRandom => "Random order",
+            Action::OrderSequential => "Sequential order",
+            Action::OrderDue => "Due cards only",
+            Action::OrderDefault => "Default order",
+            Action::DirectionForward => "Question → answer",
+            Action::DirectionReverse => "Answer → question",
+            Action::DirectionMixed => "Both ways, mixed",
+            Action::ToggleChoices => "Multiple choice",
+            Action::Choice1 => "Option 1",
+            Action::Choice2 => "Option 2",
+            Action::Choice3 => "Option 3",
+            Action::Choice4 => "Option 4",
+            Action::Submit => "Submit",
+            Action::Cancel => "Cancel",
+            Action::Review => "Review",
+            Action::NextCard => "Next (suggested rating)",
+            Action::RateAgain => "Again",
+            Action::RateHard => "Hard",
+            Action::RateGood => "Good",
+            Action::RateEasy => "Easy",
+            Action::SaveCard => "Save card",
+            Action::Discard => "Discard changes",
+            Action::Yes => "Yes",
+            Action::No => "No",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Global,
+    TopicSelect,
+    TopicCreate,
+    TopicMerge,
+    ConfirmTopic,
+    Search,
+    MainMenu,
+    CardList,
+    FindCard,
+    Export,
+    Stats,
+    Mode,
+    TagFilter,
+    TagEdit,
+    Ask,
+    Choose,
+    Reveal,
+    Editor,
+    Review,
+    Done,
+    ConfirmQuit,
+    Unsaved,
+}
+
+impl Scope {
+    pub fn label(self) -> &'static str {
+        match self {
+            Scope::Global => "Everywhere",
+            Scope::TopicSelect => "Topic Select",
+            Scope::TopicCreate => "Topic Name",
+            Scope::TopicMerge => "Merge Topics",
+            Scope::ConfirmTopic => "Confirm Topic Change",
+            Scope::Search => "Search",
+            Scope::MainMenu => "Main Menu",
+            Scope::CardList => "Card List",
+            Scope::FindCard => "Find Card",
+            Scope::Export => "Export",
+            Scope::Stats => "Statistics",
+            Scope::Mode => "Mode Select",
+            Scope::TagFilter => "Tag Filter",
+            Scope::TagEdit => "Tags",
+            Scope::Ask => "Question",
+            Scope::Choose => "Multiple Choice",
+            Scope::Reveal => "Answer",
+            Scope::Editor => "Card Editor",
+            Scope::Review => "Review",
+            Scope::Done => "Done",
+            Scope::ConfirmQuit => "Confirm Exit",
+            Scope::Unsaved => "Unsaved Changes",
+        }
+    }
+
+    // Scopes with a text field, where plain characters are typed rather than
+    // looked up in the global bindings.
+    pub fn takes_text(self) -> bool {
+        matches!(
+            self,
+            Scope::TopicCreate
+                | Scope::Search
+                | Scope::FindCard
+                | Scope::TagEdit
+                | Scope::Ask
+                | Scope::Editor
+        )
+    }
+}
+
+// Default bindings, in the order they are listed in hints and help.
+const DEFAULTS: &[(Scope, Action, &[&str])] = &[
+    (Scope::Global, Action::Help, &["?", "f1"]),
+    (Scope::Global, Action::Quit, &["ctrl+q"]),
+    (Scope::TopicSelect, Action::MoveUp, &["up"]),
+    (Scope::TopicSelect, Action::MoveDown, &["down"]),
+    (Scope::TopicSelect, Action::Open, &["enter"]),
+    (Scope::TopicSelect, Action::Expand, &["right"]),
+    (Scope::TopicSelect, Action::Collapse, &["left"]),
+    (Scope::TopicSelect, Action::CreateTopic, &["c"]),
+    (Scope::TopicSelect, Action::CreateSubtopic, &["s"]),
+    (Scope::TopicSelect, Action::RenameTopic, &["r"]),
+    (Scope::TopicSelect, Action::DuplicateTopic, &["p"]),
+    (Scope::TopicSelect, Action::DeleteTopic, &["d"]),
+    (Scope::TopicSelect, Action::MergeTopic, &["m"]),
+    (Scope::TopicSelect, Action::Search, &["/"]),
+    (Scope::TopicCreate, Action::Submit, &["enter"]),
+    (Scope::TopicCreate, Action::Cancel, &["esc"]),
+    (Scope::TopicMerge, Action::MoveUp, &["up"]),
+    (Scope::TopicMerge, Action::MoveDown, &["down"]),
+    (Scope::TopicMerge, Action::Submit, &["enter"]),
+    (Scope::TopicMerge, Action::Cancel, &["esc"]),
+    (Scope::ConfirmTopic, Action::Yes, &["y"]),
+    (Scope::ConfirmTopic, Action::No, &["n", "esc"]),
+    (Scope::Search, Action::MoveUp, &["up"]),
+    (Scope::Search, Action::MoveDown, &["down"]),
+    (Scope::Search, Action::Open, &["enter"]),
+    (Scope::Search, Action::Cancel, &["esc"]),
+    (Scope::MainMenu, Action::StartQuiz, &["s"]),
+    (Scope::MainMenu, Action::EditCards, &["e"]),
+    (Scope::MainMenu, Action::ShowStats, &["t"]),
+    (Scope::MainMenu, Action::Back, &["b"]),
+    (Scope::CardList, Action::MoveUp, &["up"]),
+    (Scope::CardList, Action::MoveDown, &["down"]),
+    (Scope::CardList, Action::EditQuestion, &["e"]),
+    (Scope::CardList, Action::EditAnswer, &["a"]),
+    (Scope::CardList, Action::AddCard, &["n"]),
+    (Scope::CardList, Action::DeleteCard, &["d"]),
+    (Scope::CardList, Action::MoveCardUp, &["shift+up"]),
+    (Scope::CardList, Action::MoveCardDown, &["shift+down"]),
+    (Scope::CardList, Action::EditTags, &["t"]),
+    (Scope::CardList, Action::FilterTags, &["f"]),
+    (Scope::CardList, Action::FindCard, &["/"]),
+    (Scope::CardList, Action::Undo, &["u", "ctrl+z"]),
+    (Scope::CardList, Action::Redo, &["ctrl+r", "ctrl+y"]),
+    (Scope::CardList, Action::Save, &["s"]),
+    (Scope::CardList, Action::Export, &["x"]),
+    (Scope::CardList, Action::Back, &["b"]),
+    (Scope::FindCard, Action::MoveUp, &["up"]),
+    (Scope::FindCard, Action::MoveDown, &["down"]),
+    (Scope::FindCard, Action::Submit, &["enter"]),
+    (Scope::FindCard, Action::Cancel, &["esc"]),
+    (Scope::Export, Action::ExportCsv, &["1"]),
+    (Scope::Export, Action::ExportTsv, &["2"]),
+    (Scope::Export, Action::ExportMarkdown, &["3"]),
+    (Scope::Export, Action::ExportJson, &["4"]),
+    (Scope::Export, Action::Cancel, &["esc"]),
+    (Scope::Stats, Action::SwitchView, &["tab"]),
+    (Scope::Stats, Action::MoveUp, &["up"]),
+    (Scope::Stats, Action::MoveDown, &["down"]),
+    (Scope::Stats, Action::Back, &["b", "esc"]),
+    (Scope::Mode, Action::OrderRandom, &["y"]),
+    (Scope::Mode, Action::OrderSequential, &["n"]),
+    (Scope::Mode, Action::OrderDue, &["d"]),
+    (Scope::Mode, Action::OrderDefault, &["enter"]),
+    (Scope::Mode, Action::DirectionForward, &["f"]),
+    (Scope::Mode, Action::DirectionReverse, &["r"]),
+    (Scope::Mode, Action::DirectionMixed, &["m"]),
+    (Scope::Mode, Action::ToggleChoices, &["c"]),
+    (Scope::TagFilter, Action::MoveUp, &["up"]),
+    (Scope::TagFilter, Action::MoveDown, &["down"]),
+    (Scope::TagFilter, Action::IncludeTag, &["i", "+"]),
+    (Scope::TagFilter, Action::ExcludeTag, &["x", "-"]),
+    (Scope::TagFilter, Action::ClearTags, &["c"]),
+    (Scope::TagFilter, Action::Submit, &["enter"]),
+    (Scope::TagFilter, Action::Back, &["esc"]),
+    (Scope::TagEdit, Action::Submit, &["enter"]),
+    (Scope::TagEdit, Action::Cancel, &["esc"]),
+    (Scope::Ask, Action::Submit, &["enter"]),
+    (Scope::Ask, Action::Review, &["ctrl+r"]),
+    (Scope::Choose, Action::Choice1, &["1"]),
+    (Scope::Choose, Action::Choice2, &["2"]),
+    (Scope::Choose, Action::Choice3, &["3"]),
+    (Scope::Choose, Action::Choice4, &["4"]),
+    (Scope::Choose, Action::MoveUp, &["up"]),
+    (Scope::Choose, Action::MoveDown, &["down"]),
+    (Scope::Choose, Action::Submit, &["enter"]),
+    (Scope::Choose, Action::Review, &["ctrl+r"]),
+    (Scope::Reveal, Action::RateAgain, &["1"]),
+    (Scope::Reveal, Action::RateHard, &["2"]),
+    (Scope::Reveal, Action::RateGood, &["3"]),
+    (Scope::Reveal, Action::RateEasy, &["4"]),
+    (Scope::Reveal, Action::NextCard, &["enter", "n"]),
+    (Scope::Reveal, Action::Review, &["ctrl+r"]),
+    (Scope::Reveal, Action::EditQuestion, &["ctrl+e"]),
+    (Scope::Reveal, Action::EditAnswer, &["ctrl+a"]),
+    (
+        Scope::Editor,
+        Action::SaveCard,
+        &["ctrl+d", "alt+enter", "ctrl+enter"],
+    ),
+    (Scope::Editor, Action::Cancel, &["esc"]),
+    (Scope::Editor, Action::Save, &["ctrl+s"]),
+    (Scope::Review, Action::MoveUp, &["up"]),
+    (Scope::Review, Action::MoveDown, &["down"]),
+    (Scope::Review, Action::Back, &["esc", "ctrl+b"]),
+    (Scope::Done, Action::Review, &["r"]),
+    (Scope::ConfirmQuit, Action::Yes, &["y"]),
+    (Scope::ConfirmQuit, Action::No, &["n"]),
+    (Scope::Unsaved, Action::Save, &["s"]),
+    (Scope::Unsaved, Action::Discard, &["d"]),
+    (Scope::Unsaved, Action::Cancel, &["c", "esc"]),
+];
+
+// One key or a list of keys, as written in the [keymap] tables.
+#[derive(Debug, Clone)]
+pub struct KeyList(pub Vec<KeySpec>);
+
+impl<'de> Deserialize<'de> for KeyList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = KeyList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a key such as \"ctrl+r\" or a list of keys")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<KeyList, E> {
+                s.parse().map(|k| KeyList(vec![k])).map_err(E::custom)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<KeyList, A::Error> {
+                let mut keys = Vec::new();
+                while let Some(k) = seq.next_element()? {
+                    keys.push(k);
+                }
+                Ok(KeyList(keys))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+pub type Overrides = HashMap<Scope, HashMap<Action, KeyList>>;
+
+// The [keymap] table: [keymap.remap] plus one table per scope.
+#[derive(Debug, Deserialize)]
+pub struct KeymapConfig {
+    #[serde(default)]
+    remap: HashMap<KeySpec, KeySpec>,
+    #[serde(flatten)]
+    scopes: Overrides,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "KeymapConfig")]
+pub struct Keymap {
+    bindings: HashMap<Scope, Vec<(Action, Vec<KeySpec>)>>,
+    // Pressing the key on the left acts as if the key on the right was pressed.
+    remaps: HashMap<KeySpec, KeySpec>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut bindings: HashMap<Scope, Vec<(Action, Vec<KeySpec>)>> = HashMap::new();
+        for (scope, action, keys) in DEFAULTS {
+            let keys = keys
+                .iter()
+                .map(|k| k.parse().expect("default key bindings are valid"))
+                .collect();
+            bindings.entry(*scope).or_default().push((*action, keys));
+        }
+        Self {
+            bindings,
+            remaps: HashMap::new(),
+        }
+    }
+}
+
+// Keys given in the config replace the defaults of that action; an empty
+// list unbinds it.
+impl TryFrom<KeymapConfig> for Keymap {
+    type Error = String;
+
+    fn try_from(config: KeymapConfig) -> Result<Self, Self::Error> {
+        let mut map = Self {
+            remaps: config.remap,
+            ..Self::default()
+        };
+        for (scope, actions) in config.scopes {
+            let bindings = map.bindings.entry(scope).or_default();
+            for (action, keys) in actions {
+                let Some(slot) = bindings.iter_mut().find(|(a, _)| *a == action) else {
+                    return Err(format!(
+                        "{} is not available in keymap.{}",
+                        action_key(action),
+                        scope_key(scope)
+                    ));
+                };
+                slot.1 = keys.0;
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl Keymap {
+    pub fn action(&self, scope: Scope, key: KeyEvent) -> Option<Action> {
+        let spec = KeySpec::from(key);
+        // Letters match either case unless a binding asks for the capital.
+        let lower = match spec.code {
+            KeyCode::Char(c) if c.is_uppercase() => Some(KeySpec {
+                code: KeyCode::Char(c.to_ascii_lowercase()),
+                ..spec
+            }),
+            _ => None,
+        };
+        let find = |scope: Scope| {
+            let bindings = self.bindings.get(&scope)?;
+            [Some(spec), lower].into_iter().flatten().find_map(|spec| {
+                bindings
+                    .iter()
+                    .find(|(_, keys)| keys.contains(&spec))
+                    .map(|(a, _)| *a)
+            })
+        };
+        find(scope).or_else(|| {
+            if spec.is_typed() && scope.takes_text() {
+                None
+            } else {
+                find(Scope::Global)
+            }
+        })
+    }
+
+    // Applies [keymap.remap]. Characters typed into a text field are left
+    // alone, so remapped letters can still be entered.
+    pub fn remap(&self, scope: Scope, key: KeyEvent) -> KeyEvent {
+        let spec = KeySpec::from(key);
+        if spec.is_typed() && scope.takes_text() {
+            return key;
+        }
+        match self.remaps.get(&spec) {
+            Some(to) => KeyEvent::new(to.code, to.modifiers),
+            None => key,
+        }
+    }
+
+    pub fn bindings(&self, scope: Scope) -> &[(Action, Vec<KeySpec>)] {
+        self.bindings.get(&scope).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // The keys bound to an action, e.g. "Enter/N", or "-" when unbound.
+    pub fn keys(&self, scope: Scope, action: Action) -> String {
+        self.bindings(scope)
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| join_keys(keys))
+            .unwrap_or_else(|| "-".into())
+    }
+
+    pub fn hint(&self, scope: Scope) -> String {
+        self.bindings(scope)
+            .iter()
+            .chain(self.bindings(Scope::Global))
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(a, keys)| format!("{}: {}", join_keys(keys), a.label()))
+            .collect::<Vec<_>>()
+            .join(" • ")
+    }
+}
+
+pub fn join_keys(keys: &[KeySpec]) -> String {
+    if keys.is_empty() {
+        return "-".into();
+    }
+    keys.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn scope_key(scope: Scope) -> String {
+    snake_case(&format!("{scope:?}"))
+}
+
+fn action_key(action: Action) -> String {
+    snake_case(&format!("{action:?}"))
+}
+
+fn snake_case(s: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+// A key with its modifiers, written like "ctrl+r", "alt+enter", "j" or "f1".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeySpec {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+// Shift is part of the character itself ('R' vs 'r'), so it is dropped for
+// character keys to match what terminals report inconsistently.
+impl From<KeyEvent> for KeySpec {
+    fn from(key: KeyEvent) -> Self {
+        let mut modifiers =
+            key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(key.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: key.code,
+            modifiers,
+        }
+    }
+}
+
+impl FromStr for KeySpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = s.split('+').collect();
+        // A trailing "+" is the plus key itself, as in "ctrl++".
+        if s.ends_with("++") || s == "+" {
+            parts.truncate(parts.len().saturating_sub(2));
+            parts.push("+");
+        }
+        let key = parts.pop().unwrap_or_default();
+        for m in parts {
+            modifiers |= match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier {m:?} in key {s:?}")),
+            };
+        }
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "space" => KeyCode::Char(' '),
+                f if f.starts_with('f') => match f[1..].parse() {
+                    Ok(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key {key:?}")),
+                },
+                _ => return Err(format!("unknown key {key:?}")),
+            },
+        };
+        // Terminals report shift+a as 'A' and shift+1 as whatever the layout
+        // puts there, so shift is only kept for letters, as the capital.
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                let mut upper = c.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(u), None) if c.is_alphabetic() => KeyCode::Char(u),
+                    _ => {
+                        return Err(format!(
+                            "shift only works with letters in key {s:?}; write the character it types instead"
+                        ))
+                    }
+                }
+            }
+            code => code,
+        };
+        Ok(Self::from(KeyEvent::new(code, modifiers)))
+    }
+}
+
+impl KeySpec {
+    // A character that would be typed into a text field.
+    fn is_typed(self) -> bool {
+        matches!(self.code, KeyCode::Char(_))
+            && !self
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl TryFrom<String> for KeySpec {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (m, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(m) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) if c.is_lowercase() => write!(f, "{}", c.to_uppercase()),
+            KeyCode::Char(c) if c.is_uppercase() => write!(f, "Shift+{c}"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDn"),
+            KeyCode::BackTab => f.write_str("Shift+Tab"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> KeyEvent {
+        let spec: KeySpec = s.parse().unwrap();
+        KeyEvent::new(spec.code, spec.modifiers)
+    }
+
+    fn keymap(toml: &str) -> Keymap {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn spec(code: KeyCode, modifiers: KeyModifiers) -> KeySpec {
+        KeySpec { code, modifiers }
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        let parse = |s: &str| s.parse::<KeySpec>();
+        assert_eq!(parse("j"), Ok(spec(KeyCode::Char('j'), KeyModifiers::NONE)));
+        assert_eq!(
+            parse("CTRL+r"),
+            Ok(spec(KeyCode::Char('r'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse("alt+enter"),
+            Ok(spec(KeyCode::Enter, KeyModifiers::ALT))
+        );
+        assert_eq!(
+            parse("PageDown"),
+            Ok(spec(KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse("space"),
+            Ok(spec(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(parse("f12"), Ok(spec(KeyCode::F(12), KeyModifiers::NONE)));
+        assert_eq!(parse("+"), Ok(spec(KeyCode::Char('+'), KeyModifiers::NONE)));
+        assert_eq!(
+            parse("ctrl++"),
+            Ok(spec(KeyCode::Char('+'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse("shift+up"),
+            Ok(spec(KeyCode::Up, KeyModifiers::SHIFT))
+        );
+        assert!(parse("f25").is_err());
+        assert!(parse("hyper+j").is_err());
+        assert!(parse("ctrl+").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn shift_letters_become_capitals() {
+        let shift_a: KeySpec = "shift+a".parse().unwrap();
+        assert_eq!(shift_a, "A".parse().unwrap());
+        assert_eq!(shift_a, KeySpec::from(key("A")));
+        assert_eq!(
+            "ctrl+shift+é".parse(),
+            Ok(spec(KeyCode::Char('É'), KeyModifiers::CONTROL))
+        );
+        assert!("shift+1".parse::<KeySpec>().is_err());
+        assert!("shift+space".parse::<KeySpec>().is_err());
+        assert_eq!(shift_a.to_string(), "Shift+A");
+        assert_eq!("a".parse::<KeySpec>().unwrap().to_string(), "A");
+
+        let map = keymap("[card_list]\nadd_card = \"shift+n\"\n");
+        let shifted = KeyEvent::new(KeyCode::Char('N'), KeyModifiers::SHIFT);
+        assert_eq!(map.action(Scope::CardList, shifted), Some(Action::AddCard));
+        assert_eq!(map.action(Scope::CardList, key("n")), None);
+    }
+
+    #[test]
+    fn tables_replace_default_keys() {
+        let map = keymap("[card_list]\ndelete_card = \"z\"\n");
+        assert_eq!(
+            map.action(Scope::CardList, key("z")),
+            Some(Action::DeleteCard)
+        );
+        assert_eq!(map.action(Scope::CardList, key("d")), None);
+        assert!(toml::from_str::<Keymap>("[card_list]\nstart_quiz = \"s\"\n").is_err());
+        assert!(toml::from_str::<Keymap>("[nowhere]\nquit = \"q\"\n").is_err());
+    }
+
+    #[test]
+    fn remap_leaves_typed_characters_alone() {
+        let map = keymap("[remap]\nj = \"down\"\n\"ctrl+n\" = \"enter\"\n");
+        assert_eq!(map.remap(Scope::CardList, key("j")), key("down"));
+        assert_eq!(map.remap(Scope::Ask, key("j")), key("j"));
+        assert_eq!(map.remap(Scope::Editor, key("j")), key("j"));
+        let enter = map.remap(Scope::Ask, key("ctrl+n"));
+        assert_eq!(enter, key("enter"));
+        assert_eq!(map.action(Scope::Ask, enter), Some(Action::Submit));
+    }
+}
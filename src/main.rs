@@ -1,93 +1,596 @@
+mod choices;
+mod cli;
+mod config;
+mod deck;
+mod export;
+mod grading;
+mod import;
+mod keymap;
+mod search;
+mod srs;
+mod stats;
+mod textinput;
+mod topics;
+mod vim;
+
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{Days, Local, NaiveDate, SubsecRound};
+use clap::{Parser, ValueEnum};
 use crossterm::{
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
-        MouseButton, MouseEventKind,
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyEvent, MouseButton, MouseEventKind,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Position, Rect},
     style::{Color, Modifier, Style},
+    symbols,
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Gauge, Paragraph, Wrap},
+    widgets::{
+        Axis, Bar, BarChart, BarGroup, Block, Borders, Chart, Clear, Dataset, Gauge, GraphType,
+        List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap,
+    },
     Terminal,
 };
+use serde::Deserialize;
 use std::{
     collections::{BTreeMap, BTreeSet},
     fs::{self, create_dir_all, File},
-    io::{self, BufRead, BufReader, Write},
-    path::PathBuf,
-    time::Duration,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
+use config::Config;
+use deck::{Card, TagFilter};
+use grading::{Grade, Grader};
+use keymap::{join_keys, Action, Scope};
+use srs::{Rating, Schedule};
+use stats::{History, Session, StatsStore};
+use textinput::TextInput;
+use topics::Topic;
+use vim::{ListMotion, Vim};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StudyOrder {
+    Sequential,
+    Random,
+    Due,
+}
+
+impl StudyOrder {
+    fn label(self) -> &'static str {
+        match self {
+            StudyOrder::Sequential => "in order",
+            StudyOrder::Random => "random order",
+            StudyOrder::Due => "due cards only",
+        }
+    }
+}
+
+// Which side of the cards is shown as the prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StudyDirection {
+    #[default]
+    Forward,
+    Reverse,
+    // Each card goes one way or the other at random.
+    Mixed,
+}
+
+impl StudyDirection {
+    fn label(self) -> &'static str {
+        match self {
+            StudyDirection::Forward => "question → answer",
+            StudyDirection::Reverse => "answer → question",
+            StudyDirection::Mixed => "both ways, mixed",
+        }
+    }
+}
+
+// A change to the deck, kept so card edits can be undone until the next save.
+// Applying one returns the change that reverts it.
+#[derive(Debug, Clone)]
+enum CardChange {
+    // Index, card, schedule and the deck the card belongs to.
+    Insert(usize, Box<Card>, Schedule, usize),
+    Remove(usize),
+    Replace(usize, Box<Card>),
+    Move(usize, usize),
+}
 
+// One topic directory. An engine opened on a topic with subtopics loads the
+// cards of all of them, the topic itself first.
+#[derive(Debug, Clone)]
+struct DeckDir {
+    dir: PathBuf,
+    title: String,
+}
 
 #[derive(Debug, Clone)]
 struct FlashCardEngine {
-    questions_file: PathBuf,
-    answers_file: PathBuf,
-    questions: Vec<String>,
-    answers: Vec<String>,
+    decks: Vec<DeckDir>,
+    cards: Vec<Card>,
+    schedule: Vec<Schedule>,
+    // Index into `decks` of the deck each card is saved to.
+    source: Vec<usize>,
+    stats: StatsStore,
+    // Only cards matching the filter are studied or listed.
+    filter: TagFilter,
     order: Vec<usize>,
+    // Position in `order` of the card being studied.
     current: usize,
+    // Whether a session is under way; card edits keep its order until it ends.
+    active: bool,
     random: bool,
+    // Cards prompted with their answer this session.
+    reversed: BTreeSet<usize>,
+    // Options of the cards asked as multiple-choice questions.
+    choices: BTreeMap<usize, Vec<String>>,
     responses: BTreeMap<usize, String>,
+    grades: BTreeMap<usize, Grade>,
+    ratings: BTreeMap<usize, Rating>,
     seen: BTreeSet<usize>,
+    // Whether the session has been added to the history.
+    in_history: bool,
+    grader: Grader,
+    shown_at: Option<Instant>,
+    undo: Vec<CardChange>,
+    redo: Vec<CardChange>,
+    // Undo depth that matches the files on disk; None once that state can no
+    // longer be reached.
+    saved: Option<usize>,
 }
 
 impl FlashCardEngine {
-    fn from_files(q: &PathBuf, a: &PathBuf) -> Result<Self> {
-        let questions = read_nonempty_lines(q)?;
-        let answers = read_nonempty_lines(a)?;
-        if questions.len() != answers.len() {
-            anyhow::bail!(
-                "Mismatched counts: {} questions vs {} answers",
-                questions.len(),
-                answers.len()
-            );
+    fn from_dir(dir: &Path) -> Result<Self> {
+        Self::load(vec![DeckDir {
+            dir: dir.to_path_buf(),
+            title: String::new(),
+        }])
+    }
+
+    // Like from_dir, but converts a legacy deck first since edits are saved.
+    fn edit_dir(dir: &Path) -> Result<Self> {
+        deck::migrate(dir)?;
+        Self::from_dir(dir)
+    }
+
+    // Opens a topic together with all of its subtopics for study, converting
+    // legacy decks on the way.
+    fn load_tree(topics_dir: &Path, topic: &Topic, all: &[Topic]) -> Result<Self> {
+        let decks: Vec<DeckDir> = std::iter::once(topic)
+            .chain(all.iter().filter(|t| topic.is_ancestor_of(t)))
+            .map(|t| DeckDir {
+                dir: topics_dir.join(&t.dir),
+                title: t.title.clone(),
+            })
+            .collect();
+        for d in &decks {
+            deck::migrate(&d.dir)?;
+        }
+        Self::load(decks)
+    }
+
+    fn load(decks: Vec<DeckDir>) -> Result<Self> {
+        let today = today();
+        let mut cards = Vec::new();
+        let mut schedule = Vec::new();
+        let mut source = Vec::new();
+        let mut stats = StatsStore::default();
+        for (i, d) in decks.iter().enumerate() {
+            let deck_cards = deck::load(&d.dir)?;
+            schedule.extend(srs::load(
+                &d.dir.join(srs::SCHEDULE_FILE),
+                &deck_cards,
+                today,
+            )?);
+            stats.extend(StatsStore::load(&d.dir.join(stats::STATS_FILE))?);
+            source.extend(std::iter::repeat_n(i, deck_cards.len()));
+            cards.extend(deck_cards);
         }
 
-        let order = (0..questions.len()).collect();
+        let order = (0..cards.len()).collect();
         Ok(Self {
-            questions_file: q.clone(),
-            answers_file: a.clone(),
-            questions,
-            answers,
+            decks,
+            cards,
+            schedule,
+            source,
+            stats,
+            filter: TagFilter::default(),
             order,
             current: 0,
+            active: false,
             random: false,
+            reversed: BTreeSet::new(),
+            choices: BTreeMap::new(),
             responses: BTreeMap::new(),
+            grades: BTreeMap::new(),
+            ratings: BTreeMap::new(),
             seen: BTreeSet::new(),
+            in_history: false,
+            grader: Grader::default(),
+            shown_at: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved: Some(0),
         })
     }
 
     fn set_random(&mut self, mode: bool) {
         self.random = mode;
-        self.order = (0..self.questions.len()).collect();
+        self.order = self.matching();
         if mode {
             let mut rng = rand::thread_rng();
             self.order.shuffle(&mut rng);
         }
         self.current = 0;
+        self.active = true;
+        self.shown_at = Some(Instant::now());
+    }
+
+    fn set_due(&mut self) {
+        let today = today();
+        self.random = false;
+        self.order = self
+            .matching()
+            .into_iter()
+            .filter(|&i| self.schedule[i].is_due(today))
+            .collect();
+        self.order.sort_by_key(|&i| self.schedule[i].due);
+        self.current = 0;
+        self.active = true;
+        self.shown_at = Some(Instant::now());
+    }
+
+    fn set_direction(&mut self, direction: StudyDirection) {
+        let mut rng = rand::thread_rng();
+        self.reversed = self
+            .order
+            .iter()
+            .copied()
+            .filter(|_| match direction {
+                StudyDirection::Forward => false,
+                StudyDirection::Reverse => true,
+                StudyDirection::Mixed => rng.gen_bool(0.5),
+            })
+            .collect();
+    }
+
+    // Distractors come from the same side of the other cards. Cards without
+    // any usable distractor are asked as usual.
+    fn set_choices(&mut self, on: bool) {
+        self.choices.clear();
+        if !on {
+            return;
+        }
+        let mut rng = rand::thread_rng();
+        for &idx in &self.order {
+            let reversed = self.reversed.contains(&idx);
+            let candidates: Vec<&str> = (0..self.cards.len())
+                .filter(|&j| j != idx)
+                .map(|j| {
+                    let c = &self.cards[j];
+                    if reversed {
+                        c.question.as_str()
+                    } else {
+                        c.answer.as_str()
+                    }
+                })
+                .collect();
+            let (_, expected) = self.sides(idx);
+            if let Some(options) = choices::options(expected, &candidates, &self.grader, &mut rng) {
+                self.choices.insert(idx, options);
+            }
+        }
+    }
+
+    fn matching(&self) -> Vec<usize> {
+        (0..self.cards.len())
+            .filter(|&i| self.filter.matches(&self.cards[i]))
+            .collect()
+    }
+
+    // Every tag in the deck with the number of cards carrying it.
+    fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for tag in self.cards.iter().flat_map(|c| &c.tags) {
+            *counts.entry(tag).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .map(|(t, n)| (t.to_string(), n))
+            .collect()
+    }
+
+    fn due_count(&self) -> usize {
+        let today = today();
+        self.schedule.iter().filter(|s| s.is_due(today)).count()
+    }
+
+    // The prompt and the expected answer of a card, swapped if it is studied
+    // in reverse.
+    fn sides(&self, idx: usize) -> (&str, &str) {
+        let c = &self.cards[idx];
+        if self.reversed.contains(&idx) {
+            (&c.answer, &c.question)
+        } else {
+            (&c.question, &c.answer)
+        }
     }
 
     fn current_card(&self) -> Option<(usize, &str, &str)> {
-        self.order
-            .get(self.current)
-            .map(|&i| (i, self.questions[i].as_str(), self.answers[i].as_str()))
+        self.order.get(self.current).map(|&i| {
+            let (prompt, expected) = self.sides(i);
+            (i, prompt, expected)
+        })
     }
 
-    fn record(&mut self, idx: usize, resp: String) {
+    fn record(&mut self, idx: usize, resp: String) -> Result<()> {
+        let (_, expected) = self.sides(idx);
+        let grade = self.grader.grade(&resp, expected);
+        let elapsed = self.shown_at.map(|t| t.elapsed()).unwrap_or_default();
+        let reversed = self.reversed.contains(&idx);
+        self.stats.entry(&self.cards[idx].id, reversed).record(
+            grade != Grade::Wrong,
+            elapsed,
+            Local::now().naive_local().trunc_subsecs(0),
+        );
+        self.grades.insert(idx, grade);
         self.responses.insert(idx, resp);
         self.seen.insert(idx);
+        self.save_stats(self.source[idx])
+    }
+
+    fn history_file(&self) -> PathBuf {
+        self.decks[0].dir.join(stats::HISTORY_FILE)
+    }
+
+    // The title of the subtopic a card comes from, if it is not the topic's own.
+    fn subtopic(&self, idx: usize) -> Option<&str> {
+        match self.source[idx] {
+            0 => None,
+            d => Some(&self.decks[d].title),
+        }
+    }
+
+    // The cards and schedules saved to one deck.
+    fn deck_part(&self, d: usize) -> (Vec<Card>, Vec<Schedule>) {
+        self.cards
+            .iter()
+            .zip(&self.schedule)
+            .zip(&self.source)
+            .filter(|(_, &s)| s == d)
+            .map(|((c, s), _)| (c.clone(), *s))
+            .unzip()
+    }
+
+    fn save_schedule(&self, d: usize) -> Result<()> {
+        let (cards, schedule) = self.deck_part(d);
+        srs::save(
+            &self.decks[d].dir.join(srs::SCHEDULE_FILE),
+            &cards,
+            &schedule,
+        )
+    }
+
+    // Statistics of cards that no longer exist stay with the topic itself.
+    fn save_stats(&self, d: usize) -> Result<()> {
+        let owner: BTreeMap<&str, usize> = self
+            .cards
+            .iter()
+            .map(|c| c.id.as_str())
+            .zip(self.source.iter().copied())
+            .collect();
+        self.stats
+            .subset(|id| owner.get(id).copied().unwrap_or(0) == d)
+            .save(&self.decks[d].dir.join(stats::STATS_FILE))
+    }
+
+    fn suggested_rating(&self, idx: usize) -> Rating {
+        match self.grades.get(&idx) {
+            Some(Grade::Correct) => Rating::Good,
+            Some(Grade::Close) => Rating::Hard,
+            _ => Rating::Again,
+        }
+    }
+
+    fn rate(&mut self, idx: usize, rating: Rating) -> Result<()> {
+        self.schedule[idx].review(rating.quality(), today());
+        self.ratings.insert(idx, rating);
+        self.save_schedule(self.source[idx])
+    }
+
+    // Adds the session to the history once, also when it is left part way
+    // so that the dashboard agrees with the per-card statistics.
+    fn finish_session(&mut self) -> Result<()> {
+        if self.in_history || self.responses.is_empty() {
+            return Ok(());
+        }
+        let correct = self.grades.values().filter(|&&g| g != Grade::Wrong).count();
+        History::append(
+            &self.history_file(),
+            Session {
+                finished: Local::now().naive_local().trunc_subsecs(0),
+                cards: self.responses.len() as u32,
+                correct: correct as u32,
+            },
+        )?;
+        self.in_history = true;
+        Ok(())
+    }
+
+    fn rating_counts(&self) -> Vec<(Rating, usize)> {
+        Rating::ALL
+            .iter()
+            .map(|&r| (r, self.ratings.values().filter(|&&x| x == r).count()))
+            .collect()
+    }
+
+    fn push_card(&mut self, tags: Vec<String>) -> usize {
+        let idx = self.cards.len();
+        let card = Card {
+            tags,
+            ..Card::new(String::new(), String::new())
+        };
+        self.change(CardChange::Insert(
+            idx,
+            Box::new(card),
+            Schedule::new(today()),
+            0,
+        ));
+        idx
+    }
+
+    fn append_cards(&mut self, cards: Vec<Card>) {
+        let today = today();
+        self.schedule
+            .extend(cards.iter().map(|_| Schedule::new(today)));
+        self.source.extend(cards.iter().map(|_| 0));
+        self.cards.extend(cards);
+        self.order = self.matching();
+    }
+
+    fn remove_card(&mut self, idx: usize) {
+        self.change(CardChange::Remove(idx));
+    }
+
+    fn set_question(&mut self, idx: usize, text: String) {
+        if self.cards[idx].question != text {
+            let card = Card {
+                question: text,
+                ..self.cards[idx].clone()
+            };
+            self.change(CardChange::Replace(idx, Box::new(card)));
+        }
+    }
+
+    fn set_answer(&mut self, idx: usize, text: String) {
+        if self.cards[idx].answer != text {
+            let card = Card {
+                answer: text,
+                ..self.cards[idx].clone()
+            };
+            self.change(CardChange::Replace(idx, Box::new(card)));
+        }
+    }
+
+    fn set_tags(&mut self, idx: usize, tags: Vec<String>) {
+        if self.cards[idx].tags != tags {
+            let card = Card {
+                tags,
+                ..self.cards[idx].clone()
+            };
+            self.change(CardChange::Replace(idx, Box::new(card)));
+        }
+    }
+
+    fn move_card(&mut self, from: usize, to: usize) {
+        if from != to && to < self.cards.len() {
+            self.change(CardChange::Move(from, to));
+        }
+    }
+
+    fn change(&mut self, change: CardChange) {
+        if self.saved.is_some_and(|n| n > self.undo.len()) {
+            self.saved = None;
+        }
+        let undo = self.apply(change);
+        self.undo.push(undo);
+        self.redo.clear();
+    }
+
+    // Returns the index of the card the undone change was about.
+    fn undo(&mut self) -> Option<usize> {
+        let change = self.undo.pop()?;
+        let redo = self.apply(change);
+        let idx = redo.index();
+        self.redo.push(redo);
+        Some(idx)
+    }
+
+    fn redo(&mut self) -> Option<usize> {
+        let change = self.redo.pop()?;
+        let undo = self.apply(change);
+        let idx = undo.index();
+        self.undo.push(undo);
+        Some(idx)
+    }
+
+    fn apply(&mut self, change: CardChange) -> CardChange {
+        let moves: Vec<Option<usize>> = (0..self.cards.len()).map(|i| change.moves(i)).collect();
+        let undo = match change {
+            CardChange::Insert(idx, card, schedule, source) => {
+                self.cards.insert(idx, *card);
+                self.schedule.insert(idx, schedule);
+                self.source.insert(idx, source);
+                CardChange::Remove(idx)
+            }
+            CardChange::Remove(idx) => {
+                let card = self.cards.remove(idx);
+                let schedule = self.schedule.remove(idx);
+                let source = self.source.remove(idx);
+                CardChange::Insert(idx, Box::new(card), schedule, source)
+            }
+            // Cards can be edited mid-session, so this keeps the study order.
+            CardChange::Replace(idx, card) => {
+                let old = std::mem::replace(&mut self.cards[idx], *card);
+                return CardChange::Replace(idx, Box::new(old));
+            }
+            CardChange::Move(from, to) => {
+                let card = self.cards.remove(from);
+                let schedule = self.schedule.remove(from);
+                let source = self.source.remove(from);
+                self.cards.insert(to, card);
+                self.schedule.insert(to, schedule);
+                self.source.insert(to, source);
+                CardChange::Move(to, from)
+            }
+        };
+        self.reindex(&moves);
+        undo
+    }
+
+    // Follows the cards to their new indices in everything kept per card
+    // during a session. Outside a session every matching card is up next;
+    // during one, the session keeps its own order and length.
+    fn reindex(&mut self, moves: &[Option<usize>]) {
+        let new = |i: &usize| moves.get(*i).copied().flatten();
+        fn remap<V>(map: &mut BTreeMap<usize, V>, new: impl Fn(&usize) -> Option<usize>) {
+            *map = std::mem::take(map)
+                .into_iter()
+                .filter_map(|(i, v)| Some((new(&i)?, v)))
+                .collect();
+        }
+        self.reversed = self.reversed.iter().filter_map(new).collect();
+        self.seen = self.seen.iter().filter_map(new).collect();
+        remap(&mut self.choices, new);
+        remap(&mut self.responses, new);
+        remap(&mut self.grades, new);
+        remap(&mut self.ratings, new);
+        if !self.active {
+            self.order = self.matching();
+            self.current = 0;
+        } else {
+            let removed_before = self.order[..self.current.min(self.order.len())]
+                .iter()
+                .filter(|i| new(i).is_none())
+                .count();
+            self.current -= removed_before;
+            self.order = self.order.iter().filter_map(new).collect();
+        }
     }
 
     fn next(&mut self) {
         self.current += 1;
+        self.active = !self.done();
+        self.shown_at = Some(Instant::now());
     }
 
     fn done(&self) -> bool {
@@ -95,141 +598,664 @@ impl FlashCardEngine {
     }
 
     fn progress(&self) -> f64 {
-        self.current as f64 / self.order.len().max(1) as f64
+        (self.current as f64 / self.order.len().max(1) as f64).min(1.0)
     }
 
-    fn save_session(&self) -> Result<PathBuf> {
+    fn save_session(&self, dir: &Path) -> Result<PathBuf> {
+        create_dir_all(dir)?;
         let ts = Local::now().format("%Y%m%d-%H%M%S");
-        let fname = format!("flashcard_responses_{ts}.txt");
-        let mut f = File::create(&fname)?;
+        let path = dir.join(format!("flashcard_responses_{ts}.txt"));
+        let mut f = File::create(&path)?;
         for (i, idx) in self.order.iter().enumerate() {
-            writeln!(f, "Q{} (#{})", i + 1, idx + 1)?;
-            writeln!(f, "{}\n", self.questions[*idx])?;
+            let (prompt, expected) = self.sides(*idx);
+            if self.reversed.contains(idx) {
+                writeln!(f, "Q{} (#{}, reversed)", i + 1, idx + 1)?;
+            } else {
+                writeln!(f, "Q{} (#{})", i + 1, idx + 1)?;
+            }
+            writeln!(f, "{prompt}\n")?;
             writeln!(
                 f,
                 "Your answer:\n{}",
                 self.responses.get(idx).unwrap_or(&"(none)".into())
             )?;
-            writeln!(f, "\nCorrect:\n{}", self.answers[*idx])?;
+            if let Some(g) = self.grades.get(idx) {
+                writeln!(f, "Result: {}", g.label())?;
+            }
+            if let Some(r) = self.ratings.get(idx) {
+                writeln!(f, "Rating: {}", r.label())?;
+            }
+            writeln!(f, "\nCorrect:\n{expected}")?;
             writeln!(f, "\n{}\n", "-".repeat(60))?;
         }
-        Ok(PathBuf::from(fname))
+        Ok(path)
+    }
+
+    fn persist_edits(&mut self) -> Result<()> {
+        for (d, deck) in self.decks.iter().enumerate() {
+            let (cards, schedule) = self.deck_part(d);
+            deck::save(&deck.dir.join(deck::DECK_FILE), &cards)?;
+            srs::save(&deck.dir.join(srs::SCHEDULE_FILE), &cards, &schedule)?;
+        }
+        self.saved = Some(self.undo.len());
+        Ok(())
     }
 
-    fn persist_edits(&self) -> Result<()> {
-        write_atomic(&self.questions_file, &self.questions)?;
-        write_atomic(&self.answers_file, &self.answers)?;
+    fn dirty(&self) -> bool {
+        self.saved != Some(self.undo.len())
+    }
+
+    // Saving from the app starts a fresh undo history; autosaves keep it.
+    fn save(&mut self) -> Result<()> {
+        self.persist_edits()?;
+        self.undo.clear();
+        self.redo.clear();
+        self.saved = Some(0);
         Ok(())
     }
 }
 
-fn read_nonempty_lines(path: &PathBuf) -> Result<Vec<String>> {
-    let f = File::open(path).with_context(|| format!("Opening {}", path.display()))?;
-    let reader = BufReader::new(f);
-    Ok(reader
-        .lines()
-        .filter_map(|l| l.ok())
+impl CardChange {
+    fn index(&self) -> usize {
+        match *self {
+            CardChange::Insert(idx, ..) | CardChange::Remove(idx) | CardChange::Replace(idx, _) => {
+                idx
+            }
+            CardChange::Move(_, to) => to,
+        }
+    }
+
+    // Where the card at index `i` ends up once the change is applied; None if
+    // it is removed.
+    fn moves(&self, i: usize) -> Option<usize> {
+        match *self {
+            CardChange::Insert(idx, ..) if i >= idx => Some(i + 1),
+            CardChange::Remove(idx) if i == idx => None,
+            CardChange::Remove(idx) if i > idx => Some(i - 1),
+            CardChange::Move(from, to) if i == from => Some(to),
+            CardChange::Move(from, to) if from < i && i <= to => Some(i - 1),
+            CardChange::Move(from, to) if to <= i && i < from => Some(i + 1),
+            _ => Some(i),
+        }
+    }
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+// Lines that are not valid UTF-8 are skipped rather than ending the file.
+fn read_nonempty_lines(path: &Path) -> Result<Vec<String>> {
+    let bytes = fs::read(path).with_context(|| format!("Opening {}", path.display()))?;
+    Ok(bytes
+        .split(|&b| b == b'\n')
+        .filter_map(|l| std::str::from_utf8(l).ok())
         .map(|l| l.trim().to_string())
         .filter(|l| !l.is_empty())
         .collect())
 }
 
-fn write_atomic(path: &PathBuf, lines: &[String]) -> Result<()> {
-    let tmp = path.with_extension("tmp");
-    {
-        let mut f = File::create(&tmp)?;
-        for l in lines {
-            writeln!(f, "{l}")?;
-        }
-    }
-    fs::rename(tmp, path)?;
-    Ok(())
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    // deck.toml.tmp rather than deck.tmp, so files sharing a stem do not
+    // share a temporary file.
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents)
+        .and_then(|()| fs::rename(&tmp, path))
+        .with_context(|| format!("Writing {}", path.display()))
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum Screen {
     Mode,
+    TagFilter,
+    EditTags,
     Ask,
+    Choose,
     Reveal,
     Review,
     EditQuestion,
     EditAnswer,
     Done,
     TopicSelect,
-    TopicCreate,
+    TopicName,
+    TopicMerge,
+    ConfirmTopic,
+    Search,
     MainMenu,
     CardList,
+    FindCard,
+    Export,
+    Stats,
     ConfirmQuit,
+    Unsaved,
+}
+
+// The topic operation being named, picked or confirmed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TopicOp {
+    // The parent of the new topic, if it is a subtopic.
+    Create(Option<Topic>),
+    Rename(Topic),
+    Duplicate(Topic),
+    Delete(Topic),
+    PickMerge(Topic),
+    Merge(Topic, Topic),
+}
+
+// What to do once unsaved changes are saved or discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Leave {
+    Quit,
+    CloseTopic,
+}
+
+impl Screen {
+    fn scope(self) -> Scope {
+        match self {
+            Screen::Mode => Scope::Mode,
+            Screen::TagFilter => Scope::TagFilter,
+            Screen::EditTags => Scope::TagEdit,
+            Screen::Ask => Scope::Ask,
+            Screen::Choose => Scope::Choose,
+            Screen::Reveal => Scope::Reveal,
+            Screen::Review => Scope::Review,
+            Screen::EditQuestion | Screen::EditAnswer => Scope::Editor,
+            Screen::Done => Scope::Done,
+            Screen::TopicSelect => Scope::TopicSelect,
+            Screen::TopicName => Scope::TopicCreate,
+            Screen::TopicMerge => Scope::TopicMerge,
+            Screen::ConfirmTopic => Scope::ConfirmTopic,
+            Screen::Search => Scope::Search,
+            Screen::MainMenu => Scope::MainMenu,
+            Screen::CardList => Scope::CardList,
+            Screen::FindCard => Scope::FindCard,
+            Screen::Export => Scope::Export,
+            Screen::Stats => Scope::Stats,
+            Screen::ConfirmQuit => Scope::ConfirmQuit,
+            Screen::Unsaved => Scope::Unsaved,
+        }
+    }
 }
 
 struct App {
+    config: Config,
+    topics_dir: PathBuf,
+    sessions_dir: PathBuf,
+    trash_dir: PathBuf,
+    exports_dir: PathBuf,
     eng: Option<FlashCardEngine>,
     screen: Screen,
-    input: String,
-    cursor: usize,
+    input: TextInput,
     review_scroll: u16,
-    topics: Vec<String>,
+    topics: Vec<Topic>,
     selected_topic: usize,
-    topic_input: String,
-    current_topic: Option<String>,
+    // Directories of the topics whose subtopics are hidden.
+    collapsed: BTreeSet<String>,
+    topic_op: Option<TopicOp>,
+    current_topic: Option<Topic>,
+    // Cards of every topic, in the order of `topics`, while searching.
+    search_index: Vec<Vec<Card>>,
+    search_hits: Vec<search::Hit>,
+    selected_hit: usize,
     in_edit_mode: bool,
     selected_card: usize,
+    // Index of the card open in the editor.
+    editing: usize,
+    // Words the card list is narrowed down to.
+    card_query: String,
+    card_list: ListState,
+    selected_tag: usize,
+    // The order picked in Mode while tags are being chosen.
+    study_order: Option<StudyOrder>,
+    direction: StudyDirection,
+    multiple_choice: bool,
+    selected_choice: usize,
     prev_screen: Option<Screen>,
+    leaving: Option<Leave>,
+    dirty_since: Option<Instant>,
+    notice: Option<String>,
+    dashboard: Option<Dashboard>,
+    show_card_stats: bool,
+    show_help: bool,
+    vim: Vim,
+}
+
+struct Dashboard {
+    // (days since the first recorded session, accuracy in percent)
+    accuracy: Vec<(f64, f64)>,
+    first_day: Option<NaiveDate>,
+    last_day: Option<NaiveDate>,
+    per_day: Vec<(String, u64)>,
+    hardest: Vec<(String, f64, u32)>,
+    mastery: Vec<(String, u64)>,
 }
 
 impl App {
-    fn new() -> Self {
+    fn new(paths: config::Paths, config: Config) -> Self {
+        let direction = config.default_direction.unwrap_or_default();
+        let multiple_choice = config.multiple_choice;
         Self {
+            config,
+            topics_dir: paths.topics_dir,
+            sessions_dir: paths.sessions_dir,
+            trash_dir: paths.trash_dir,
+            exports_dir: paths.exports_dir,
             eng: None,
             screen: Screen::TopicSelect,
-            input: String::new(),
-            cursor: 0,
+            input: TextInput::default(),
             review_scroll: 0,
             topics: Vec::new(),
             selected_topic: 0,
-            topic_input: String::new(),
+            collapsed: BTreeSet::new(),
+            topic_op: None,
             current_topic: None,
+            search_index: Vec::new(),
+            search_hits: Vec::new(),
+            selected_hit: 0,
             in_edit_mode: false,
             selected_card: 0,
+            editing: 0,
+            card_query: String::new(),
+            card_list: ListState::default(),
+            selected_tag: 0,
+            study_order: None,
+            direction,
+            multiple_choice,
+            selected_choice: 0,
             prev_screen: None,
+            leaving: None,
+            dirty_since: None,
+            notice: None,
+            dashboard: None,
+            show_card_stats: false,
+            show_help: false,
+            vim: Vim::default(),
+        }
+    }
+
+    fn start_editing(&mut self, text: String) {
+        self.input = TextInput::new(text);
+        self.vim.reset();
+    }
+
+    fn close_topic(&mut self) {
+        if let Some(Err(e)) = self.eng.as_mut().map(FlashCardEngine::finish_session) {
+            self.notice = Some(format!("{e:#}"));
+        }
+        self.card_query.clear();
+        self.eng = None;
+        self.current_topic = None;
+        self.screen = Screen::TopicSelect;
+    }
+
+    // A failed save is shown as a notice and the changes stay unsaved.
+    fn save_deck(&mut self) -> bool {
+        let Some(eng) = &mut self.eng else {
+            return true;
+        };
+        match eng.save() {
+            Ok(()) => true,
+            Err(e) => {
+                self.notice = Some(format!("Save failed: {e:#}"));
+                false
+            }
+        }
+    }
+
+    // Saves the deck once it has had unsaved changes for the configured time.
+    fn autosave(&mut self) {
+        let (Some(secs), Some(eng)) = (self.config.autosave, &mut self.eng) else {
+            return;
+        };
+        if !eng.dirty() {
+            self.dirty_since = None;
+            return;
+        }
+        let since = *self.dirty_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= Duration::from_secs(secs.get()) {
+            self.dirty_since = None;
+            if let Err(e) = eng.persist_edits() {
+                self.notice = Some(format!("Autosave failed: {e:#}"));
+            }
         }
     }
 
     fn load_topics(&mut self) -> Result<()> {
-        create_dir_all("topics")?;
-        self.topics = fs::read_dir("topics")?
-            .filter_map(|res| res.ok())
-            .filter(|e| e.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
-            .map(|e| e.file_name().to_string_lossy().into_owned())
-            .collect();
-        self.topics.sort();
+        self.topics = topics::list(&self.topics_dir)?;
         Ok(())
     }
 
-    fn load_eng(&mut self, topic: &str) -> Result<()> {
-        let path = PathBuf::from("topics").join(topic);
-        create_dir_all(&path)?;
-        let q = path.join("questions.txt");
-        let a = path.join("answers.txt");
-        if !q.exists() {
-            File::create(&q)?;
+    fn select_topic(&mut self, dir: &str) {
+        if let Some(i) = self.topics.iter().position(|t| t.dir == dir) {
+            self.selected_topic = i;
+        }
+        self.selected_topic = self.selected_topic.min(self.topics.len().saturating_sub(1));
+        // Make sure the selection is not inside a collapsed topic.
+        if let Some(topic) = self.topics.get(self.selected_topic) {
+            self.collapsed.retain(|dir| {
+                !topic
+                    .dir
+                    .strip_prefix(dir.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+            });
+        }
+    }
+
+    fn topic_path(&self, topic: &Topic) -> String {
+        topics::title_path(&self.topics, topic)
+    }
+
+    // Indices of the topics not hidden inside a collapsed one.
+    fn visible_topics(&self) -> Vec<usize> {
+        (0..self.topics.len())
+            .filter(|&i| {
+                !self
+                    .topics
+                    .iter()
+                    .any(|t| self.collapsed.contains(&t.dir) && t.is_ancestor_of(&self.topics[i]))
+            })
+            .collect()
+    }
+
+    fn has_subtopics(&self, i: usize) -> bool {
+        self.topics
+            .get(i + 1)
+            .is_some_and(|t| self.topics[i].is_ancestor_of(t))
+    }
+
+    fn move_topic(&mut self, motion: ListMotion) {
+        let visible = self.visible_topics();
+        let Some(pos) = visible.iter().position(|&i| i == self.selected_topic) else {
+            self.selected_topic = visible.first().copied().unwrap_or(0);
+            return;
+        };
+        let pos = match motion {
+            ListMotion::Up => pos.saturating_sub(1),
+            ListMotion::Down => (pos + 1).min(visible.len() - 1),
+            ListMotion::Top => 0,
+            ListMotion::Bottom => visible.len() - 1,
+        };
+        self.selected_topic = visible[pos];
+    }
+
+    // Where the typed topic name would be stored, or why it cannot be used.
+    fn check_topic_name(&self) -> Result<String> {
+        let (parent, except) = match &self.topic_op {
+            Some(TopicOp::Rename(t)) | Some(TopicOp::Duplicate(t)) => {
+                (t.parent(), Some(t.dir.as_str()))
+            }
+            Some(TopicOp::Create(parent)) => (parent.as_ref().map(|p| p.dir.as_str()), None),
+            _ => (None, None),
+        };
+        let except = except.filter(|_| matches!(self.topic_op, Some(TopicOp::Rename(_))));
+        topics::dir_for(&self.topics, parent, self.input.text(), except)
+    }
+
+    // Reports the outcome of a topic operation on the hint bar and selects
+    // `select` in the refreshed list, if it is there.
+    fn finish_topic_op(&mut self, result: Result<String>, select: &str) {
+        self.notice = Some(match result {
+            Ok(msg) => msg,
+            Err(e) => format!("{e:#}"),
+        });
+        if let Err(e) = self.load_topics() {
+            self.notice = Some(format!("{e:#}"));
+        }
+        self.select_topic(select);
+        self.screen = Screen::TopicSelect;
+    }
+
+    fn load_eng(&mut self, topic: &Topic) -> Result<()> {
+        create_dir_all(self.topics_dir.join(&topic.dir))?;
+        let mut eng = FlashCardEngine::load_tree(&self.topics_dir, topic, &self.topics)?;
+        eng.grader.threshold = self.config.strictness.threshold();
+        self.eng = Some(eng);
+        self.current_topic = Some(topic.clone());
+        self.card_query.clear();
+        self.card_list = ListState::default();
+        Ok(())
+    }
+
+    fn update_search(&mut self) {
+        self.search_hits = search::run(&self.search_index, self.input.text());
+        self.selected_hit = 0;
+    }
+
+    fn close_search(&mut self) {
+        self.search_index = Vec::new();
+        self.search_hits = Vec::new();
+        self.input.take();
+    }
+
+    // Cards shown in the card list: those matching the tag filter and
+    // containing every word of the query in the question, answer or tags.
+    fn visible_cards(&self) -> Vec<usize> {
+        let Some(eng) = &self.eng else {
+            return Vec::new();
+        };
+        let terms = search::terms(&self.card_query);
+        eng.matching()
+            .into_iter()
+            .filter(|&i| {
+                let c = &eng.cards[i];
+                terms.iter().all(|t| {
+                    search::contains(&c.question, t)
+                        || search::contains(&c.answer, t)
+                        || c.tags.iter().any(|tag| search::contains(tag, t))
+                })
+            })
+            .collect()
+    }
+
+    fn move_card_cursor(&mut self, motion: ListMotion) {
+        let visible = self.visible_cards();
+        let Some(pos) = visible.iter().position(|&i| i == self.selected_card) else {
+            self.fix_card_selection();
+            return;
+        };
+        let pos = match motion {
+            ListMotion::Up => pos.saturating_sub(1),
+            ListMotion::Down => (pos + 1).min(visible.len() - 1),
+            ListMotion::Top => 0,
+            ListMotion::Bottom => visible.len() - 1,
+        };
+        self.selected_card = visible[pos];
+    }
+
+    // Moves the selection to the nearest listed card if it is hidden.
+    fn fix_card_selection(&mut self) {
+        let visible = self.visible_cards();
+        if !visible.contains(&self.selected_card) {
+            self.selected_card = visible
+                .iter()
+                .find(|&&i| i > self.selected_card)
+                .or(visible.last())
+                .copied()
+                .unwrap_or(0);
+        }
+    }
+
+    // Asks which tags to study first if the deck has any.
+    fn choose_order(&mut self, order: StudyOrder) {
+        if self
+            .eng
+            .as_ref()
+            .is_some_and(|e| !e.tag_counts().is_empty())
+        {
+            self.study_order = Some(order);
+            self.selected_tag = 0;
+            self.screen = Screen::TagFilter;
+        } else {
+            self.begin_session(order);
         }
-        if !a.exists() {
-            File::create(&a)?;
+    }
+
+    fn begin_session(&mut self, order: StudyOrder) {
+        let Some(eng) = &mut self.eng else {
+            return;
+        };
+        match order {
+            StudyOrder::Sequential => eng.set_random(false),
+            StudyOrder::Random => eng.set_random(true),
+            StudyOrder::Due => eng.set_due(),
+        }
+        if let Some(n) = self.config.session_size {
+            eng.order.truncate(n.get());
         }
-        self.eng = Some(FlashCardEngine::from_files(&q, &a)?);
-        self.current_topic = Some(topic.to_string());
+        eng.set_direction(self.direction);
+        eng.set_choices(self.multiple_choice);
+        if eng.order.is_empty() {
+            eng.active = false;
+            let filtered = !eng.filter.is_empty();
+            self.notice = Some(match order {
+                StudyOrder::Due if filtered => "No cards with these tags are due today.".into(),
+                StudyOrder::Due => "No cards are due today.".into(),
+                _ if filtered => "No cards match these tags.".into(),
+                _ => "This topic has no cards yet.".into(),
+            });
+        } else {
+            self.ask();
+        }
+    }
+
+    // Multiple-choice questions for cards with options, typed answers otherwise.
+    fn ask_screen(&self) -> Screen {
+        let choose = self.eng.as_ref().is_some_and(|e| {
+            e.current_card()
+                .is_some_and(|(i, _, _)| e.choices.contains_key(&i))
+        });
+        if choose {
+            Screen::Choose
+        } else {
+            Screen::Ask
+        }
+    }
+
+    fn ask(&mut self) {
+        self.start_editing(String::new());
+        self.selected_choice = 0;
+        self.screen = self.ask_screen();
+    }
+
+    fn load_dashboard(&mut self) -> Result<()> {
+        let Some(eng) = &self.eng else {
+            return Ok(());
+        };
+        let today = today();
+        let days = History::load(&eng.history_file())?.by_day();
+        let first_day = days.keys().next().copied();
+        let last_day = days.keys().next_back().copied();
+        let accuracy = days
+            .iter()
+            .map(|(d, (cards, correct))| {
+                let x = first_day.map(|f| (*d - f).num_days()).unwrap_or(0) as f64;
+                (x, *correct as f64 * 100.0 / (*cards).max(1) as f64)
+            })
+            .collect();
+        let per_day = (0..14u64)
+            .rev()
+            .map(|back| {
+                let d = today.checked_sub_days(Days::new(back)).unwrap_or(today);
+                let n = days.get(&d).map(|(cards, _)| *cards).unwrap_or(0);
+                (d.format("%d").to_string(), n as u64)
+            })
+            .collect();
+
+        let mut hardest: Vec<(String, f64, u32)> = eng
+            .cards
+            .iter()
+            .filter_map(|c| {
+                let st = eng.stats.get(&c.id).filter(|st| st.seen > 0)?;
+                Some((first_line(&c.question), st.accuracy(), st.seen))
+            })
+            .collect();
+        hardest.sort_by(|a, b| a.1.total_cmp(&b.1).then(b.2.cmp(&a.2)));
+        hardest.truncate(10);
+
+        let mastery = self
+            .topics
+            .iter()
+            .filter_map(|t| {
+                let dir = self.topics_dir.join(&t.dir);
+                let cards = deck::load(&dir).ok()?;
+                let schedule = srs::load(&dir.join(srs::SCHEDULE_FILE), &cards, today).ok()?;
+                let mature = schedule.iter().filter(|s| s.is_mature()).count();
+                Some((t.title.clone(), (mature * 100 / cards.len().max(1)) as u64))
+            })
+            .collect();
+
+        self.dashboard = Some(Dashboard {
+            accuracy,
+            first_day,
+            last_day,
+            per_day,
+            hardest,
+            mastery,
+        });
         Ok(())
     }
 }
 
 fn main() -> Result<()> {
-    let mut app = App::new();
+    let cli = cli::Cli::parse();
+    let (config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(e) => (Config::default(), Some(format!("{e:#} (using defaults)"))),
+    };
+    let hint_legacy = cli.topics_dir.is_none() && cli.migrate_from.is_none();
+    let paths = config::resolve(
+        cli.data_dir,
+        cli.topics_dir,
+        cli.migrate_from.as_deref(),
+        &config,
+    )?;
+    let legacy_hint = config::legacy_hint(&paths).filter(|_| hint_legacy);
+    let mut app = App::new(paths, config);
+    match cli.command {
+        None => {}
+        Some(cli::Command::Study(cli::StudyArgs {
+            topic,
+            order,
+            direction,
+            choices,
+        })) => {
+            let topic = topics::find(&app.topics_dir, &topic)?;
+            if let Some(direction) = direction {
+                app.direction = direction;
+            }
+            app.multiple_choice |= choices;
+            app.load_topics()?;
+            app.load_eng(&topic)?;
+            app.screen = Screen::Mode;
+            if let Some(order) = order.or(app.config.default_order) {
+                app.choose_order(order);
+            }
+        }
+        Some(cli::Command::Deck(command)) => {
+            if let Some(e) = config_error {
+                eprintln!("Warning: {e}");
+            }
+            if let Some(hint) = legacy_hint {
+                eprintln!("{hint}");
+            }
+            return cli::run(command, &app.topics_dir);
+        }
+    }
+    if config_error.is_some() || legacy_hint.is_some() {
+        app.notice = config_error.or(legacy_hint);
+    }
     app.load_topics()?;
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = ratatui::backend::CrosstermBackend::new(stdout);
     let mut term = Terminal::new(backend)?;
 
@@ -239,14 +1265,21 @@ fn main() -> Result<()> {
     execute!(
         term.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     term.show_cursor()?;
 
-    if app.screen == Screen::Done {
+    // Cards answered before quitting part way still go into the history.
+    if let Some(Err(e)) = app.eng.as_mut().map(FlashCardEngine::finish_session) {
+        eprintln!("Could not save the session history: {e:#}");
+    }
+
+    // Quitting from the summary goes through a confirmation screen first.
+    if app.screen == Screen::Done || app.prev_screen == Some(Screen::Done) {
         if let Some(eng) = &app.eng {
             if !eng.responses.is_empty() {
-                if let Ok(p) = eng.save_session() {
+                if let Ok(p) = eng.save_session(&app.sessions_dir) {
                     eprintln!("Saved session: {}", p.display());
                 }
             }
@@ -261,30 +1294,46 @@ fn run_app(
     app: &mut App,
 ) -> Result<()> {
     loop {
+        app.autosave();
         term.draw(|f| ui(f, app))?;
         if crossterm::event::poll(Duration::from_millis(100))? {
             let event = event::read()?;
             match event {
                 Event::Key(key) => {
+                    let key = app.config.keymap.remap(app.screen.scope(), key);
                     if handle(app, key)? {
                         break;
                     }
                 }
-                Event::Mouse(mouse) => {
-                    if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
-                        let size = term.size()?;
-                        let layout = Layout::default()
-                            .direction(Direction::Vertical)
-                            .constraints([
-                                Constraint::Length(1),
-                                Constraint::Min(10),
-                                Constraint::Length(3),
-                                Constraint::Length(3),
-                            ])
-                            .split(size);
-                        if layout[3].contains(Position::new(mouse.column, mouse.row)) {
-                            app.screen = Screen::Review;
-                        }
+                Event::Paste(text) if !app.show_help => match app.screen {
+                    Screen::Ask | Screen::TopicName | Screen::EditTags => {
+                        app.input.paste(&text, false)
+                    }
+                    Screen::Search => {
+                        app.input.paste(&text, false);
+                        app.update_search();
+                    }
+                    Screen::FindCard => {
+                        app.input.paste(&text, false);
+                        app.card_query = app.input.text().to_string();
+                        app.fix_card_selection();
+                    }
+                    Screen::EditQuestion | Screen::EditAnswer => app.input.paste(&text, true),
+                    _ => {}
+                },
+                Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                    let size = term.size()?;
+                    let layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([
+                            Constraint::Length(1),
+                            Constraint::Min(10),
+                            Constraint::Length(3),
+                            Constraint::Length(3),
+                        ])
+                        .split(size);
+                    if layout[3].contains(Position::new(mouse.column, mouse.row)) {
+                        app.screen = Screen::Review;
                     }
                 }
                 _ => {}
@@ -295,280 +1344,648 @@ fn run_app(
 }
 
 fn handle(app: &mut App, key: KeyEvent) -> Result<bool> {
-    if key.code == KeyCode::Char('q') && key.modifiers.contains(KeyModifiers::CONTROL) {
-        app.prev_screen = Some(app.screen);
-        app.screen = Screen::ConfirmQuit;
+    app.notice = None;
+    let scope = app.screen.scope();
+    let action = app.config.keymap.action(scope, key);
+    // Any key closes the help overlay; only quitting goes through.
+    if app.show_help {
+        app.show_help = false;
+        if action != Some(Action::Quit) {
+            return Ok(false);
+        }
+    }
+    match action {
+        Some(Action::Help) => {
+            app.show_help = true;
+            return Ok(false);
+        }
+        Some(Action::Quit) if !matches!(app.screen, Screen::ConfirmQuit | Screen::Unsaved) => {
+            app.prev_screen = Some(app.screen);
+            if app.eng.as_ref().is_some_and(FlashCardEngine::dirty) {
+                app.leaving = Some(Leave::Quit);
+                app.screen = Screen::Unsaved;
+            } else {
+                app.screen = Screen::ConfirmQuit;
+            }
+            return Ok(false);
+        }
+        _ => {}
+    }
+
+    if app.config.vim {
+        match app.screen {
+            Screen::TopicSelect
+            | Screen::TopicMerge
+            | Screen::CardList
+            | Screen::TagFilter
+            | Screen::Choose => {
+                if let Some(motion) = app.vim.list_motion(key) {
+                    match app.screen {
+                        Screen::CardList => app.move_card_cursor(motion),
+                        Screen::Choose => {
+                            let len = app.eng.as_ref().map_or(0, |e| {
+                                e.current_card()
+                                    .and_then(|(i, _, _)| e.choices.get(&i))
+                                    .map_or(0, Vec::len)
+                            });
+                            let selected = &mut app.selected_choice;
+                            *selected = match motion {
+                                ListMotion::Up => selected.saturating_sub(1),
+                                ListMotion::Down => (*selected + 1).min(len.saturating_sub(1)),
+                                ListMotion::Top => 0,
+                                ListMotion::Bottom => len.saturating_sub(1),
+                            };
+                        }
+                        Screen::TagFilter => {
+                            let len = app.eng.as_ref().map_or(0, |e| e.tag_counts().len());
+                            let selected = &mut app.selected_tag;
+                            *selected = match motion {
+                                ListMotion::Up => selected.saturating_sub(1),
+                                ListMotion::Down => (*selected + 1).min(len.saturating_sub(1)),
+                                ListMotion::Top => 0,
+                                ListMotion::Bottom => len.saturating_sub(1),
+                            };
+                        }
+                        _ => app.move_topic(motion),
+                    }
+                    return Ok(false);
+                }
+            }
+            Screen::Ask | Screen::EditTags | Screen::EditQuestion | Screen::EditAnswer
+                if app.vim.edit(key, &mut app.input) =>
+            {
+                return Ok(false);
+            }
+            _ => {}
+        }
     }
 
     match app.screen {
-        Screen::TopicSelect => match key.code {
-            KeyCode::Up => app.selected_topic = app.selected_topic.saturating_sub(1),
-            KeyCode::Down => {
-                app.selected_topic =
-                    (app.selected_topic + 1).min(app.topics.len().saturating_sub(1))
+        Screen::TopicSelect => match action {
+            Some(Action::MoveUp) => app.move_topic(ListMotion::Up),
+            Some(Action::MoveDown) => app.move_topic(ListMotion::Down),
+            Some(Action::Open) if !app.topics.is_empty() => {
+                let topic = app.topics[app.selected_topic].clone();
+                match app.load_eng(&topic) {
+                    Ok(()) => app.screen = Screen::MainMenu,
+                    Err(e) => app.notice = Some(format!("{e:#}")),
+                }
             }
-            KeyCode::Enter => {
-                if !app.topics.is_empty() {
-                    let topic = app.topics[app.selected_topic].clone();
-                    app.load_eng(&topic)?;
-                    app.screen = Screen::MainMenu;
+            Some(Action::Search) => match search::index(&app.topics_dir, &app.topics) {
+                Ok(index) => {
+                    app.search_index = index;
+                    app.start_editing(String::new());
+                    app.update_search();
+                    app.screen = Screen::Search;
                 }
+                Err(e) => app.notice = Some(format!("{e:#}")),
+            },
+            Some(Action::CreateTopic) => {
+                app.topic_op = Some(TopicOp::Create(None));
+                app.start_editing(String::new());
+                app.screen = Screen::TopicName;
             }
-            KeyCode::Char('c') | KeyCode::Char('C') => {
-                app.topic_input.clear();
-                app.cursor = 0;
-                app.screen = Screen::TopicCreate;
+            Some(a) if !app.topics.is_empty() => {
+                let topic = app.topics[app.selected_topic].clone();
+                match a {
+                    Action::CreateSubtopic => {
+                        app.topic_op = Some(TopicOp::Create(Some(topic)));
+                        app.start_editing(String::new());
+                        app.screen = Screen::TopicName;
+                    }
+                    Action::Expand => {
+                        app.collapsed.remove(&topic.dir);
+                    }
+                    // Collapsing a topic that shows no subtopics moves to its parent.
+                    Action::Collapse
+                        if app.has_subtopics(app.selected_topic)
+                            && !app.collapsed.contains(&topic.dir) =>
+                    {
+                        app.collapsed.insert(topic.dir);
+                    }
+                    Action::Collapse => {
+                        if let Some(parent) = topic.parent() {
+                            let parent = parent.to_string();
+                            app.select_topic(&parent);
+                        }
+                    }
+                    Action::RenameTopic => {
+                        app.start_editing(topic.title.clone());
+                        app.topic_op = Some(TopicOp::Rename(topic));
+                        app.screen = Screen::TopicName;
+                    }
+                    Action::DuplicateTopic => {
+                        app.start_editing(format!("{} copy", topic.title));
+                        app.topic_op = Some(TopicOp::Duplicate(topic));
+                        app.screen = Screen::TopicName;
+                    }
+                    Action::DeleteTopic => {
+                        app.topic_op = Some(TopicOp::Delete(topic));
+                        app.screen = Screen::ConfirmTopic;
+                    }
+                    Action::MergeTopic if app.has_subtopics(app.selected_topic) => {
+                        app.notice = Some(format!(
+                            "{} has subtopics; merge or move them first",
+                            topic.title
+                        ))
+                    }
+                    Action::MergeTopic if app.topics.len() > 1 => {
+                        app.topic_op = Some(TopicOp::PickMerge(topic));
+                        app.selected_topic = usize::from(app.selected_topic == 0);
+                        app.screen = Screen::TopicMerge;
+                    }
+                    Action::MergeTopic => {
+                        app.notice = Some("There is no other topic to merge into".into())
+                    }
+                    _ => {}
+                }
             }
             _ => {}
         },
 
-        Screen::TopicCreate => match key.code {
-            KeyCode::Enter => {
-                let name = std::mem::take(&mut app.topic_input);
-                if !name.is_empty() {
-                    app.load_eng(&name)?;
-                    app.topics.push(name);
-                    app.topics.sort();
-                    app.screen = Screen::MainMenu;
-                } else {
-                    app.screen = Screen::TopicSelect;
+        Screen::Search => match action {
+            Some(Action::MoveUp) => app.selected_hit = app.selected_hit.saturating_sub(1),
+            Some(Action::MoveDown) => {
+                app.selected_hit =
+                    (app.selected_hit + 1).min(app.search_hits.len().saturating_sub(1))
+            }
+            // Opens the card in the editor of the topic it belongs to.
+            Some(Action::Open) if !app.search_hits.is_empty() => {
+                let hit = &app.search_hits[app.selected_hit];
+                let topic = app.topics[hit.topic].clone();
+                let id = hit.card.id.clone();
+                if let Err(e) = app.load_eng(&topic) {
+                    app.notice = Some(format!("{e:#}"));
+                    return Ok(false);
                 }
+                app.close_search();
+                app.select_topic(&topic.dir);
+                app.selected_card = app
+                    .eng
+                    .as_ref()
+                    .and_then(|e| e.cards.iter().position(|c| c.id == id))
+                    .unwrap_or(0);
+                app.in_edit_mode = true;
+                app.screen = Screen::CardList;
             }
-            KeyCode::Esc => app.screen = Screen::TopicSelect,
-            KeyCode::Char(c) => {
-                app.topic_input.insert(app.cursor, c);
-                app.cursor += 1;
+            Some(Action::Cancel) => {
+                app.close_search();
+                app.screen = Screen::TopicSelect;
             }
-            KeyCode::Backspace => {
-                if app.cursor > 0 {
-                    app.topic_input.remove(app.cursor - 1);
-                    app.cursor -= 1;
+            _ => {
+                let before = app.input.text().to_string();
+                app.input.handle_key(key, false);
+                if app.input.text() != before {
+                    app.update_search();
                 }
             }
-            KeyCode::Delete => {
-                if app.cursor < app.topic_input.len() {
-                    app.topic_input.remove(app.cursor);
+        },
+
+        Screen::TopicName => match action {
+            Some(Action::Submit) if app.input.text().trim().is_empty() => {
+                app.topic_op = None;
+                app.screen = Screen::TopicSelect;
+            }
+            // Problems with the name are shown below the input until fixed.
+            Some(Action::Submit) if app.check_topic_name().is_ok() => {
+                let title = app.input.take();
+                match app.topic_op.take() {
+                    Some(TopicOp::Rename(from)) => {
+                        let result = topics::rename(&app.topics_dir, &from, &title);
+                        let select = result.as_ref().map_or(&from.dir, |t| &t.dir).clone();
+                        let result =
+                            result.map(|t| format!("Renamed {} to {}", from.title, t.title));
+                        app.finish_topic_op(result, &select);
+                    }
+                    Some(TopicOp::Duplicate(from)) => {
+                        let result = topics::duplicate(&app.topics_dir, &from, &title);
+                        let select = result.as_ref().map_or(&from.dir, |t| &t.dir).clone();
+                        let result =
+                            result.map(|t| format!("Copied {} to {}", from.title, t.title));
+                        app.finish_topic_op(result, &select);
+                    }
+                    Some(TopicOp::Create(parent)) => {
+                        match topics::create(&app.topics_dir, parent.as_ref(), &title) {
+                            Ok(topic) => {
+                                match app.load_topics().and_then(|()| app.load_eng(&topic)) {
+                                    Ok(()) => {
+                                        app.select_topic(&topic.dir);
+                                        app.screen = Screen::MainMenu;
+                                    }
+                                    Err(e) => app.finish_topic_op(Err(e), &topic.dir),
+                                }
+                            }
+                            Err(e) => app.finish_topic_op(Err(e), ""),
+                        }
+                    }
+                    _ => app.screen = Screen::TopicSelect,
                 }
             }
-            KeyCode::Left => app.cursor = app.cursor.saturating_sub(1),
-            KeyCode::Right => app.cursor = (app.cursor + 1).min(app.topic_input.len()),
+            Some(Action::Submit) => {}
+            Some(Action::Cancel) => {
+                app.topic_op = None;
+                app.screen = Screen::TopicSelect;
+            }
+            _ => {
+                app.input.handle_key(key, false);
+            }
+        },
+
+        Screen::TopicMerge => match action {
+            Some(Action::MoveUp) => app.move_topic(ListMotion::Up),
+            Some(Action::MoveDown) => app.move_topic(ListMotion::Down),
+            Some(Action::Submit) => {
+                if let Some(TopicOp::PickMerge(from)) = &app.topic_op {
+                    let into = app.topics[app.selected_topic].clone();
+                    if *from == into {
+                        app.notice = Some("Pick another topic to merge into".into());
+                    } else {
+                        app.topic_op = Some(TopicOp::Merge(from.clone(), into));
+                        app.screen = Screen::ConfirmTopic;
+                    }
+                }
+            }
+            Some(Action::Cancel) => {
+                if let Some(TopicOp::PickMerge(from)) = app.topic_op.take() {
+                    app.selected_topic = app.topics.iter().position(|t| *t == from).unwrap_or(0);
+                }
+                app.screen = Screen::TopicSelect;
+            }
+            _ => {}
+        },
+
+        Screen::ConfirmTopic => match action {
+            Some(Action::Yes) => match app.topic_op.take() {
+                Some(TopicOp::Delete(name)) => {
+                    let result = topics::trash(&app.topics_dir, &app.trash_dir, &name)
+                        .map(|p| format!("Moved {} to {}", name.title, p.display()));
+                    app.finish_topic_op(result, "");
+                }
+                Some(TopicOp::Merge(from, into)) => {
+                    let result =
+                        topics::merge(&app.topics_dir, &app.trash_dir, &from, &into).map(|n| {
+                            format!("Merged {n} card(s) from {} into {}", from.title, into.title)
+                        });
+                    app.finish_topic_op(result, &into.dir);
+                }
+                _ => app.screen = Screen::TopicSelect,
+            },
+            Some(Action::No) => {
+                app.topic_op = None;
+                app.screen = Screen::TopicSelect;
+            }
             _ => {}
         },
 
-        Screen::MainMenu => match key.code {
-            KeyCode::Char('s') | KeyCode::Char('S') => {
+        Screen::MainMenu => match action {
+            Some(Action::StartQuiz) => {
                 app.in_edit_mode = false;
                 app.screen = Screen::Mode;
             }
-            KeyCode::Char('e') | KeyCode::Char('E') => {
+            Some(Action::EditCards) => {
                 app.in_edit_mode = true;
                 app.screen = Screen::CardList;
             }
-            KeyCode::Char('b') | KeyCode::Char('B') => {
-                app.eng = None;
-                app.current_topic = None;
-                app.screen = Screen::TopicSelect;
+            Some(Action::ShowStats) => match app.load_dashboard() {
+                Ok(()) => {
+                    app.review_scroll = 0;
+                    app.show_card_stats = false;
+                    app.screen = Screen::Stats;
+                }
+                Err(e) => app.notice = Some(format!("{e:#}")),
+            },
+            Some(Action::Back) if app.eng.as_ref().is_some_and(FlashCardEngine::dirty) => {
+                app.prev_screen = Some(app.screen);
+                app.leaving = Some(Leave::CloseTopic);
+                app.screen = Screen::Unsaved;
             }
+            Some(Action::Back) => app.close_topic(),
             _ => {}
         },
 
-        Screen::CardList => match key.code {
-            KeyCode::Up => app.selected_card = app.selected_card.saturating_sub(1),
-            KeyCode::Down => {
-                if let Some(eng) = &app.eng {
-                    app.selected_card =
-                        (app.selected_card + 1).min(eng.questions.len().saturating_sub(1))
-                }
-            }
-            KeyCode::Char('e') | KeyCode::Char('E') => {
+        Screen::CardList => match action {
+            Some(Action::MoveUp) => app.move_card_cursor(ListMotion::Up),
+            Some(Action::MoveDown) => app.move_card_cursor(ListMotion::Down),
+            Some(Action::EditQuestion) => {
                 if let Some(eng) = &mut app.eng {
-                    if app.selected_card < eng.questions.len() {
-                        eng.current = app.selected_card;
-                        app.input = eng.questions[app.selected_card].clone();
-                        app.cursor = app.input.len();
+                    if app.selected_card < eng.cards.len() {
+                        app.editing = app.selected_card;
+                        let text = eng.cards[app.selected_card].question.clone();
+                        app.start_editing(text);
                         app.screen = Screen::EditQuestion;
                     }
                 }
             }
-            KeyCode::Char('a') | KeyCode::Char('A') => {
+            Some(Action::EditAnswer) => {
                 if let Some(eng) = &mut app.eng {
-                    if app.selected_card < eng.questions.len() {
-                        eng.current = app.selected_card;
-                        app.input = eng.answers[app.selected_card].clone();
-                        app.cursor = app.input.len();
+                    if app.selected_card < eng.cards.len() {
+                        app.editing = app.selected_card;
+                        let text = eng.cards[app.selected_card].answer.clone();
+                        app.start_editing(text);
                         app.screen = Screen::EditAnswer;
                     }
                 }
             }
-            KeyCode::Char('n') | KeyCode::Char('N') => {
+            Some(Action::AddCard) => {
                 if let Some(eng) = &mut app.eng {
-                    eng.questions.push(String::new());
-                    eng.answers.push(String::new());
-                    eng.order = (0..eng.questions.len()).collect();
-                    let new_idx = eng.questions.len() - 1;
-                    eng.current = new_idx;
+                    // Keep the new card visible under the current filter.
+                    let new_idx = eng.push_card(eng.filter.include.iter().cloned().collect());
+                    app.editing = new_idx;
                     app.selected_card = new_idx;
-                    app.input.clear();
-                    app.cursor = 0;
+                    app.start_editing(String::new());
                     app.screen = Screen::EditQuestion;
                 }
             }
-            KeyCode::Char('d') | KeyCode::Char('D') => {
+            Some(Action::DeleteCard) => {
                 if let Some(eng) = &mut app.eng {
-                    if app.selected_card < eng.questions.len() {
-                        eng.questions.remove(app.selected_card);
-                        eng.answers.remove(app.selected_card);
-                        eng.order = (0..eng.questions.len()).collect();
-                    }
-                    if app.selected_card >= eng.questions.len() {
-                        app.selected_card = eng.questions.len().saturating_sub(1);
+                    if app.selected_card < eng.cards.len() {
+                        eng.remove_card(app.selected_card);
                     }
                 }
+                app.fix_card_selection();
             }
-            KeyCode::Char('s') | KeyCode::Char('S') => {
-                if let Some(eng) = &mut app.eng {
-                    eng.persist_edits()?;
+            // Cards swap places with their neighbour in the (filtered) list.
+            Some(a @ (Action::MoveCardUp | Action::MoveCardDown)) => {
+                let visible = app.visible_cards();
+                let pos = visible.iter().position(|&i| i == app.selected_card);
+                let to = match (a, pos) {
+                    (Action::MoveCardUp, Some(p)) if p > 0 => Some(visible[p - 1]),
+                    (Action::MoveCardDown, Some(p)) => visible.get(p + 1).copied(),
+                    _ => None,
+                };
+                if let (Some(eng), Some(to)) = (&mut app.eng, to) {
+                    eng.move_card(app.selected_card, to);
+                    app.selected_card = to;
                 }
             }
-            KeyCode::Char('b') | KeyCode::Char('B') => app.screen = Screen::MainMenu,
-            _ => {}
-        },
-
-        Screen::Mode => match key.code {
-            KeyCode::Char('y') | KeyCode::Char('Y') => {
-                if let Some(eng) = &mut app.eng {
-                    eng.set_random(true);
-                    app.screen = Screen::Ask;
+            Some(Action::EditTags) => {
+                if let Some(eng) = &app.eng {
+                    if let Some(card) = eng.cards.get(app.selected_card) {
+                        let text = card.tags.join(" ");
+                        app.start_editing(text);
+                        app.screen = Screen::EditTags;
+                    }
+                }
+            }
+            Some(Action::FindCard) => {
+                let query = app.card_query.clone();
+                app.start_editing(query);
+                app.screen = Screen::FindCard;
+            }
+            Some(Action::FilterTags) => {
+                if app.eng.as_ref().is_some_and(|e| e.tag_counts().is_empty()) {
+                    app.notice = Some("No card has tags yet".into());
+                } else {
+                    app.selected_tag = 0;
+                    app.screen = Screen::TagFilter;
                 }
             }
-            KeyCode::Char('n') | KeyCode::Char('N') => {
+            Some(a @ (Action::Undo | Action::Redo)) => {
                 if let Some(eng) = &mut app.eng {
-                    eng.set_random(false);
-                    app.screen = Screen::Ask;
+                    let idx = if a == Action::Undo {
+                        eng.undo()
+                    } else {
+                        eng.redo()
+                    };
+                    match idx {
+                        Some(idx) => app.selected_card = idx.min(eng.cards.len().saturating_sub(1)),
+                        None if a == Action::Undo => app.notice = Some("Nothing to undo".into()),
+                        None => app.notice = Some("Nothing to redo".into()),
+                    }
                 }
+                app.fix_card_selection();
+            }
+            Some(Action::Save) => {
+                app.save_deck();
             }
+            Some(Action::Export) => app.screen = Screen::Export,
+            Some(Action::Back) => app.screen = Screen::MainMenu,
             _ => {}
         },
 
-        Screen::Ask => match key.code {
-            KeyCode::Enter => {
-                if let Some(eng) = &mut app.eng {
-                    if let Some((idx, _, _)) = eng.current_card() {
-                        let resp = std::mem::take(&mut app.input);
-                        eng.record(idx, resp);
-                        app.cursor = 0;
-                        app.screen = Screen::Reveal;
-                    }
-                }
+        // The list narrows down as the query is typed; Enter keeps it.
+        Screen::FindCard => match action {
+            Some(Action::MoveUp) => app.move_card_cursor(ListMotion::Up),
+            Some(Action::MoveDown) => app.move_card_cursor(ListMotion::Down),
+            Some(Action::Submit) => {
+                app.input.take();
+                app.screen = Screen::CardList;
             }
-            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                app.screen = Screen::Review
+            Some(Action::Cancel) => {
+                app.input.take();
+                app.card_query.clear();
+                app.screen = Screen::CardList;
             }
-            KeyCode::Char(c) => {
-                app.input.insert(app.cursor, c);
-                app.cursor += 1;
+            _ => {
+                app.input.handle_key(key, false);
+                if app.input.text() != app.card_query {
+                    app.card_query = app.input.text().to_string();
+                    app.fix_card_selection();
+                }
             }
-            KeyCode::Backspace => {
-                if app.cursor > 0 {
-                    app.input.remove(app.cursor - 1);
-                    app.cursor -= 1;
+        },
+
+        Screen::Export => {
+            if let Some(format) = action.and_then(Action::export_format) {
+                if let (Some(eng), Some(topic)) = (&app.eng, &app.current_topic) {
+                    let path = app.exports_dir.join(format!(
+                        "{}.{}",
+                        topic.dir.replace('/', "-"),
+                        format.extension()
+                    ));
+                    let result = fs::create_dir_all(&app.exports_dir)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|()| export::export(&eng.cards, format, &path))
+                        .with_context(|| format!("Exporting to {}", path.display()));
+                    app.notice = Some(match result {
+                        Ok(()) => {
+                            format!("Exported {} card(s) to {}", eng.cards.len(), path.display())
+                        }
+                        Err(e) => format!("{e:#}"),
+                    });
                 }
+                app.screen = Screen::CardList;
+            } else if action == Some(Action::Cancel) {
+                app.screen = Screen::CardList;
             }
-            KeyCode::Delete => {
-                if app.cursor < app.input.len() {
-                    app.input.remove(app.cursor);
+        }
+
+        Screen::Stats => match action {
+            Some(Action::MoveUp) => app.review_scroll = app.review_scroll.saturating_sub(1),
+            Some(Action::MoveDown) => {
+                if let Some(eng) = &app.eng {
+                    let last = eng.cards.len().saturating_sub(1) as u16;
+                    app.review_scroll = (app.review_scroll + 1).min(last);
                 }
             }
-            KeyCode::Left => app.cursor = app.cursor.saturating_sub(1),
-            KeyCode::Right => app.cursor = (app.cursor + 1).min(app.input.len()),
+            Some(Action::SwitchView) => app.show_card_stats = !app.show_card_stats,
+            Some(Action::Back) => {
+                app.dashboard = None;
+                app.screen = Screen::MainMenu
+            }
             _ => {}
         },
 
-        Screen::Reveal => match key.code {
-            KeyCode::Enter | KeyCode::Char('n') | KeyCode::Char('N') => {
-                if let Some(eng) = &mut app.eng {
-                    eng.next();
-                    if eng.done() {
-                        app.screen = Screen::Done;
+        Screen::Mode => match action {
+            Some(Action::OrderRandom) => app.choose_order(StudyOrder::Random),
+            Some(Action::OrderSequential) => app.choose_order(StudyOrder::Sequential),
+            Some(Action::OrderDue) => app.choose_order(StudyOrder::Due),
+            Some(Action::OrderDefault) => {
+                let order = app.config.default_order.unwrap_or(StudyOrder::Sequential);
+                app.choose_order(order);
+            }
+            Some(Action::DirectionForward) => app.direction = StudyDirection::Forward,
+            Some(Action::DirectionReverse) => app.direction = StudyDirection::Reverse,
+            Some(Action::DirectionMixed) => app.direction = StudyDirection::Mixed,
+            Some(Action::ToggleChoices) => app.multiple_choice = !app.multiple_choice,
+            _ => {}
+        },
+
+        // Shared by the study setup (`study_order` is set) and the card list.
+        Screen::TagFilter => {
+            let Some(eng) = &mut app.eng else {
+                return Ok(false);
+            };
+            let tags = eng.tag_counts();
+            match action {
+                Some(Action::MoveUp) => app.selected_tag = app.selected_tag.saturating_sub(1),
+                Some(Action::MoveDown) => {
+                    app.selected_tag = (app.selected_tag + 1).min(tags.len().saturating_sub(1))
+                }
+                Some(a @ (Action::IncludeTag | Action::ExcludeTag)) => {
+                    if let Some((tag, _)) = tags.get(app.selected_tag) {
+                        eng.filter.toggle(tag, a == Action::IncludeTag);
+                    }
+                }
+                Some(Action::ClearTags) => eng.filter = TagFilter::default(),
+                Some(Action::Submit) => match app.study_order {
+                    Some(order) => {
+                        app.begin_session(order);
+                        if matches!(app.screen, Screen::Ask | Screen::Choose) {
+                            app.study_order = None;
+                        }
+                    }
+                    None => {
+                        app.fix_card_selection();
+                        app.screen = Screen::CardList;
+                    }
+                },
+                Some(Action::Back) => {
+                    if app.study_order.take().is_some() {
+                        app.screen = Screen::Mode;
                     } else {
-                        app.input.clear();
-                        app.screen = Screen::Ask;
+                        app.fix_card_selection();
+                        app.screen = Screen::CardList;
                     }
                 }
+                _ => {}
             }
-            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                app.screen = Screen::Review
-            }
-            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+        }
+
+        Screen::EditTags => match action {
+            Some(Action::Submit) => {
                 if let Some(eng) = &mut app.eng {
-                    if let Some((_, q, _)) = eng.current_card() {
-                        app.input = q.to_string();
-                        app.cursor = app.input.len();
-                        app.screen = Screen::EditQuestion;
-                    }
+                    let tags = deck::parse_tags(&app.input.take());
+                    eng.set_tags(app.selected_card, tags);
                 }
+                app.fix_card_selection();
+                app.screen = Screen::CardList;
             }
-            KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::Cancel) => app.screen = Screen::CardList,
+            _ => {
+                app.input.handle_key(key, false);
+            }
+        },
+
+        Screen::Ask => match action {
+            Some(Action::Submit) => {
                 if let Some(eng) = &mut app.eng {
-                    if let Some((_, _, a)) = eng.current_card() {
-                        app.input = a.to_string();
-                        app.cursor = app.input.len();
-                        app.screen = Screen::EditAnswer;
+                    if let Some((idx, _, _)) = eng.current_card() {
+                        let resp = app.input.take();
+                        if let Err(e) = eng.record(idx, resp) {
+                            app.notice = Some(format!("{e:#}"));
+                        }
+                        app.screen = Screen::Reveal;
                     }
                 }
             }
-            _ => {}
+            Some(Action::Review) => app.screen = Screen::Review,
+            _ => {
+                app.input.handle_key(key, false);
+            }
         },
 
-        Screen::EditQuestion => match key.code {
-            KeyCode::Enter => {
-                if let Some(eng) = &mut app.eng {
-                    let idx = eng.current;
-                    eng.questions[idx] = app.input.clone();
+        Screen::Choose => {
+            let Some(eng) = &mut app.eng else {
+                return Ok(false);
+            };
+            let Some((idx, _, _)) = eng.current_card() else {
+                return Ok(false);
+            };
+            let count = eng.choices.get(&idx).map_or(0, Vec::len);
+            let pick = match action {
+                Some(Action::MoveUp) => {
+                    app.selected_choice = app.selected_choice.saturating_sub(1);
+                    None
                 }
-                app.screen = if app.in_edit_mode {
-                    Screen::CardList
-                } else {
-                    Screen::Reveal
-                };
+                Some(Action::MoveDown) => {
+                    app.selected_choice = (app.selected_choice + 1).min(count.saturating_sub(1));
+                    None
+                }
+                Some(Action::Review) => {
+                    app.screen = Screen::Review;
+                    None
+                }
+                Some(Action::Submit) => Some(app.selected_choice),
+                Some(a) => a.choice(),
+                None => None,
+            };
+            let option = pick.and_then(|n| eng.choices.get(&idx)?.get(n).cloned());
+            if let Some(option) = option {
+                if let Err(e) = eng.record(idx, option) {
+                    app.notice = Some(format!("{e:#}"));
+                }
+                app.screen = Screen::Reveal;
             }
-            KeyCode::Esc => {
-                app.screen = if app.in_edit_mode {
-                    Screen::CardList
-                } else {
-                    Screen::Reveal
+        }
+
+        Screen::Reveal => match action {
+            Some(Action::NextCard) => advance(app, None),
+            Some(a) if a.rating().is_some() => advance(app, a.rating()),
+            Some(Action::Review) => app.screen = Screen::Review,
+            Some(Action::EditQuestion) => {
+                if let Some(eng) = &mut app.eng {
+                    if let Some((idx, _, _)) = eng.current_card() {
+                        app.editing = idx;
+                        let text = eng.cards[idx].question.clone();
+                        app.start_editing(text);
+                        app.screen = Screen::EditQuestion;
+                    }
                 }
             }
-            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::EditAnswer) => {
                 if let Some(eng) = &mut app.eng {
-                    eng.persist_edits()?;
-                }
-            }
-            KeyCode::Char(c) => {
-                app.input.insert(app.cursor, c);
-                app.cursor += 1;
-            }
-            KeyCode::Backspace => {
-                if app.cursor > 0 {
-                    app.input.remove(app.cursor - 1);
-                    app.cursor -= 1;
-                }
-            }
-            KeyCode::Delete => {
-                if app.cursor < app.input.len() {
-                    app.input.remove(app.cursor);
+                    if let Some((idx, _, _)) = eng.current_card() {
+                        app.editing = idx;
+                        let text = eng.cards[idx].answer.clone();
+                        app.start_editing(text);
+                        app.screen = Screen::EditAnswer;
+                    }
                 }
             }
-            KeyCode::Left => app.cursor = app.cursor.saturating_sub(1),
-            KeyCode::Right => app.cursor = (app.cursor + 1).min(app.input.len()),
             _ => {}
         },
 
-        Screen::EditAnswer => match key.code {
-            KeyCode::Enter => {
+        Screen::EditQuestion | Screen::EditAnswer => match action {
+            Some(Action::SaveCard) => {
                 if let Some(eng) = &mut app.eng {
-                    let idx = eng.current;
-                    eng.answers[idx] = app.input.clone();
+                    let idx = app.editing;
+                    let text = app.input.text().to_string();
+                    if app.screen == Screen::EditQuestion {
+                        eng.set_question(idx, text);
+                    } else {
+                        eng.set_answer(idx, text);
+                    }
                 }
                 app.screen = if app.in_edit_mode {
                     Screen::CardList
@@ -576,74 +1993,42 @@ fn handle(app: &mut App, key: KeyEvent) -> Result<bool> {
                     Screen::Reveal
                 };
             }
-            KeyCode::Esc => {
+            Some(Action::Cancel) => {
                 app.screen = if app.in_edit_mode {
                     Screen::CardList
                 } else {
                     Screen::Reveal
                 }
             }
-            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if let Some(eng) = &mut app.eng {
-                    eng.persist_edits()?;
-                }
-            }
-            KeyCode::Char(c) => {
-                app.input.insert(app.cursor, c);
-                app.cursor += 1;
-            }
-            KeyCode::Backspace => {
-                if app.cursor > 0 {
-                    app.input.remove(app.cursor - 1);
-                    app.cursor -= 1;
-                }
+            Some(Action::Save) => {
+                app.save_deck();
             }
-            KeyCode::Delete => {
-                if app.cursor < app.input.len() {
-                    app.input.remove(app.cursor);
-                }
+            _ => {
+                app.input.handle_key(key, true);
             }
-            KeyCode::Left => app.cursor = app.cursor.saturating_sub(1),
-            KeyCode::Right => app.cursor = (app.cursor + 1).min(app.input.len()),
-            _ => {}
         },
 
-        Screen::Review => match key.code {
-            KeyCode::Up => app.review_scroll = app.review_scroll.saturating_sub(1),
-            KeyCode::Down => app.review_scroll = app.review_scroll.saturating_add(1),
-            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                app.screen = if let Some(eng) = &app.eng {
-                    if eng.done() {
-                        Screen::Done
-                    } else {
-                        Screen::Ask
-                    }
-                } else {
-                    Screen::Ask
-                };
-            }
-            KeyCode::Esc => {
-                app.screen = if let Some(eng) = &app.eng {
-                    if eng.done() {
-                        Screen::Done
-                    } else {
-                        Screen::Ask
-                    }
-                } else {
-                    Screen::Ask
+        Screen::Review => match action {
+            Some(Action::MoveUp) => app.review_scroll = app.review_scroll.saturating_sub(1),
+            Some(Action::MoveDown) => app.review_scroll = app.review_scroll.saturating_add(1),
+            Some(Action::Back) => {
+                app.screen = match &app.eng {
+                    Some(eng) if eng.done() => Screen::Done,
+                    _ => app.ask_screen(),
                 };
             }
             _ => {}
         },
 
-        Screen::Done => match key.code {
-            KeyCode::Char('r') | KeyCode::Char('R') => app.screen = Screen::Review,
-            _ => {}
-        },
+        Screen::Done => {
+            if action == Some(Action::Review) {
+                app.screen = Screen::Review;
+            }
+        }
 
-        Screen::ConfirmQuit => match key.code {
-            KeyCode::Char('y') | KeyCode::Char('Y') => return Ok(true),
-            KeyCode::Char('n') | KeyCode::Char('N') => {
+        Screen::ConfirmQuit => match action {
+            Some(Action::Yes) => return Ok(true),
+            Some(Action::No) => {
                 if let Some(prev) = app.prev_screen {
                     app.screen = prev;
                 }
@@ -651,10 +2036,57 @@ fn handle(app: &mut App, key: KeyEvent) -> Result<bool> {
             }
             _ => {}
         },
+
+        Screen::Unsaved => {
+            match action {
+                // Stays on the prompt if saving fails.
+                Some(Action::Save) => {
+                    if !app.save_deck() {
+                        return Ok(false);
+                    }
+                }
+                Some(Action::Discard) => {}
+                Some(Action::Cancel) => {
+                    app.screen = app.prev_screen.take().unwrap_or(Screen::MainMenu);
+                    app.leaving = None;
+                    return Ok(false);
+                }
+                _ => return Ok(false),
+            }
+            match app.leaving.take() {
+                Some(Leave::Quit) => return Ok(true),
+                Some(Leave::CloseTopic) | None => {
+                    app.prev_screen = None;
+                    app.close_topic();
+                }
+            }
+        }
     }
     Ok(false)
 }
 
+// Write errors are shown as a notice; the session goes on regardless.
+fn advance(app: &mut App, rating: Option<Rating>) {
+    let Some(eng) = &mut app.eng else {
+        return;
+    };
+    let mut result = Ok(());
+    if let Some((idx, _, _)) = eng.current_card() {
+        let rating = rating.unwrap_or_else(|| eng.suggested_rating(idx));
+        result = eng.rate(idx, rating);
+    }
+    eng.next();
+    if eng.done() {
+        result = result.and(eng.finish_session());
+        app.screen = Screen::Done;
+    } else {
+        app.ask();
+    }
+    if let Err(e) = result {
+        app.notice = Some(format!("{e:#}"));
+    }
+}
+
 fn ui(f: &mut ratatui::Frame, app: &mut App) {
     let size = f.size();
     let layout = Layout::default()
@@ -667,10 +2099,18 @@ fn ui(f: &mut ratatui::Frame, app: &mut App) {
         ])
         .split(size);
 
-    let title = Paragraph::new("Flashcards • Rust Edition")
+    let theme = app.config.theme.clone();
+    let keymap = &app.config.keymap;
+    let mut title = "Flashcards • Rust Edition".to_string();
+    if let (Some(eng), Some(topic)) = (&app.eng, &app.current_topic) {
+        if eng.dirty() {
+            title.push_str(&format!(" • {} [modified]", app.topic_path(topic)));
+        }
+    }
+    let title = Paragraph::new(title)
         .style(
             Style::default()
-                .fg(Color::Yellow)
+                .fg(theme.title)
                 .add_modifier(Modifier::BOLD),
         )
         .alignment(Alignment::Center);
@@ -682,85 +2122,235 @@ fn ui(f: &mut ratatui::Frame, app: &mut App) {
             let inner = block.inner(layout[1]);
             f.render_widget(block, layout[1]);
             let text: Vec<Line> = if app.topics.is_empty() {
-                vec![Line::from(Span::raw("No topics yet. Press C to create."))]
+                vec![Line::from(format!(
+                    "No topics yet. Press {} to create one.",
+                    keymap.keys(Scope::TopicSelect, Action::CreateTopic)
+                ))]
             } else {
-                app.topics
-                    .iter()
-                    .enumerate()
-                    .map(|(i, t)| {
-                        let style = if i == app.selected_topic {
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD)
-                        } else {
-                            Style::default()
-                        };
-                        Line::from(Span::styled(t.clone(), style))
-                    })
-                    .collect()
+                topic_lines(app, None)
             };
             let para = Paragraph::new(text)
                 .alignment(Alignment::Left)
                 .wrap(Wrap::default());
             f.render_widget(para, inner);
         }
-        Screen::TopicCreate => {
-            let block = Block::default()
-                .borders(Borders::ALL)
-                .title("Create New Topic");
+        Screen::TopicMerge => {
+            let from = match &app.topic_op {
+                Some(TopicOp::PickMerge(from)) => Some(from),
+                _ => None,
+            };
+            let block = Block::default().borders(Borders::ALL).title(format!(
+                "Merge {} into…",
+                from.map_or("", |t| t.title.as_str())
+            ));
+            let inner = block.inner(layout[1]);
+            f.render_widget(block, layout[1]);
+            let para = Paragraph::new(topic_lines(app, from))
+                .alignment(Alignment::Left)
+                .wrap(Wrap::default());
+            f.render_widget(para, inner);
+        }
+        Screen::ConfirmTopic => {
+            let msg = match &app.topic_op {
+                Some(TopicOp::Delete(topic)) => {
+                    let subtopics = app
+                        .topics
+                        .iter()
+                        .filter(|t| topic.is_ancestor_of(t))
+                        .count();
+                    let what = match subtopics {
+                        0 => topic.title.clone(),
+                        n => format!("{} and its {n} subtopic(s)", topic.title),
+                    };
+                    format!("Delete {what}? It is moved to {}.", app.trash_dir.display())
+                }
+                Some(TopicOp::Merge(from, into)) => format!(
+                    "Merge {0} into {1}? The cards of {0} are added to {1} \
+                     and {0} is moved to {2}.",
+                    from.title,
+                    into.title,
+                    app.trash_dir.display()
+                ),
+                _ => String::new(),
+            };
+            let msg = format!(
+                "{msg}\n\n({}/{})",
+                keymap.keys(Scope::ConfirmTopic, Action::Yes),
+                keymap.keys(Scope::ConfirmTopic, Action::No)
+            );
+            draw_modal(f, size, &msg, "Confirm")
+        }
+        Screen::Search => draw_search(f, layout[1], app),
+        Screen::TopicName => {
+            let title = match &app.topic_op {
+                Some(TopicOp::Rename(from)) => format!("Rename {}", from.title),
+                Some(TopicOp::Duplicate(from)) => format!("Duplicate {}", from.title),
+                Some(TopicOp::Create(Some(parent))) => {
+                    format!("New Subtopic of {}", app.topic_path(parent))
+                }
+                _ => "Create New Topic".to_string(),
+            };
+            let block = Block::default().borders(Borders::ALL).title(title);
             let inner = block.inner(layout[1]);
             f.render_widget(block, layout[1]);
-            let hint = Paragraph::new("Enter topic name, then press Enter")
+            let mut lines = vec![Line::styled(
+                format!(
+                    "Enter topic name, then press {}",
+                    keymap.keys(Scope::TopicCreate, Action::Submit)
+                ),
+                Style::default().fg(theme.hint),
+            )];
+            if !app.input.text().trim().is_empty() {
+                lines.push(Line::from(""));
+                lines.push(match app.check_topic_name() {
+                    Ok(dir) => Line::styled(
+                        format!("Stored in {}", app.topics_dir.join(dir).display()),
+                        Style::default().fg(theme.hint),
+                    ),
+                    Err(e) => Line::styled(e.to_string(), Style::default().fg(theme.wrong)),
+                });
+            }
+            let hint = Paragraph::new(lines)
                 .alignment(Alignment::Center)
-                .style(Style::default().fg(Color::DarkGray));
+                .wrap(Wrap { trim: false });
             f.render_widget(hint, inner);
         }
         Screen::MainMenu => {
             let msg = if let Some(topic) = &app.current_topic {
+                let subtopics = match app.eng.as_ref().map_or(0, |e| e.decks.len() - 1) {
+                    0 => String::new(),
+                    n => format!("\nIncluding {n} subtopic(s)"),
+                };
                 format!(
-                    "Selected topic: {}\n\nS: Start Quiz\nE: Edit Cards\nB: Back to topics",
-                    topic
+                    "Selected topic: {}{subtopics}\n\n{}",
+                    app.topic_path(topic),
+                    key_menu(app, Scope::MainMenu, |a| a.label().to_string())
                 )
             } else {
                 "Error: No topic selected".to_string()
             };
             draw_modal(f, size, &msg, "Main Menu");
         }
-        Screen::CardList => {
-            let block = Block::default().borders(Borders::ALL).title("Edit Cards");
-            let inner = block.inner(layout[1]);
-            f.render_widget(block, layout[1]);
-            let text: Vec<Line> = if let Some(eng) = &app.eng {
-                eng.questions
-                    .iter()
-                    .enumerate()
-                    .map(|(i, q)| {
-                        let style = if i == app.selected_card {
-                            Style::default()
-                                .fg(Color::Yellow)
-                                .add_modifier(Modifier::BOLD)
-                        } else {
-                            Style::default()
-                        };
-                        Line::from(Span::styled(format!("Card {}: {}", i + 1, q), style))
-                    })
-                    .collect()
+        Screen::CardList | Screen::FindCard | Screen::EditTags => {
+            let mut state = std::mem::take(&mut app.card_list);
+            draw_card_list(f, layout[1], app, &mut state);
+            app.card_list = state;
+        }
+        Screen::TagFilter => {
+            let title = if app.study_order.is_some() {
+                "Study Cards Tagged…"
             } else {
-                vec![]
+                "Filter Cards by Tag"
             };
+            let block = Block::default().borders(Borders::ALL).title(title);
+            let inner = block.inner(layout[1]);
+            f.render_widget(block, layout[1]);
+            let mut text = Vec::new();
+            if let Some(eng) = &app.eng {
+                text.push(Line::styled(
+                    format!(
+                        "{} of {} cards match. Cards need one of the included tags \
+                         (if any) and none of the excluded ones.",
+                        eng.matching().len(),
+                        eng.cards.len()
+                    ),
+                    Style::default().fg(theme.hint),
+                ));
+                text.push(Line::from(""));
+                for (i, (tag, n)) in eng.tag_counts().into_iter().enumerate() {
+                    let mark = if eng.filter.include.contains(&tag) {
+                        "[+]"
+                    } else if eng.filter.exclude.contains(&tag) {
+                        "[-]"
+                    } else {
+                        "[ ]"
+                    };
+                    let style = if i == app.selected_tag {
+                        Style::default()
+                            .fg(theme.selected)
+                            .add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    text.push(Line::styled(format!("{mark} {tag} ({n})"), style));
+                }
+            }
             let para = Paragraph::new(text)
                 .alignment(Alignment::Left)
-                .wrap(Wrap { trim: true });
+                .wrap(Wrap::default());
             f.render_widget(para, inner);
         }
-        Screen::Mode => draw_modal(f, size, "Study in random order? (Y/N)", "Mode Select"),
-        Screen::Ask => draw_ask(f, layout[1], app),
+        Screen::Mode => {
+            let due = app.eng.as_ref().map(|e| e.due_count()).unwrap_or(0);
+            let default = app.config.default_order.unwrap_or(StudyOrder::Sequential);
+            let menu = key_menu(app, Scope::Mode, |a| match a {
+                Action::OrderDue => format!("{} ({due} due today)", a.label()),
+                Action::OrderDefault => format!("{} ({})", a.label(), default.label()),
+                _ if a.direction() == Some(app.direction) => format!("{} ✓", a.label()),
+                Action::ToggleChoices if app.multiple_choice => format!("{} ✓", a.label()),
+                _ => a.label().to_string(),
+            });
+            let msg = format!(
+                "How do you want to study?\nCards go {}.\n\n{menu}",
+                app.direction.label()
+            );
+            draw_modal(f, size, &msg, "Mode Select")
+        }
+        Screen::Export => {
+            let menu = key_menu(app, Scope::Export, |a| match a.export_format() {
+                Some(fmt) => format!("{} (.{})", fmt.label(), fmt.extension()),
+                None => a.label().to_string(),
+            });
+            let msg = format!("Export cards as:\n\n{menu}");
+            draw_modal(f, size, &msg, "Export")
+        }
+        Screen::Stats if app.show_card_stats => draw_card_stats(f, layout[1], app),
+        Screen::Stats => draw_dashboard(f, layout[1], app),
+        Screen::Ask | Screen::Choose => draw_ask(f, layout[1], app),
         Screen::Reveal => draw_reveal(f, layout[1], app),
         Screen::Review => draw_review(f, layout[1], app),
         Screen::EditQuestion => draw_editor(f, layout[1], app, true),
         Screen::EditAnswer => draw_editor(f, layout[1], app, false),
-        Screen::Done => draw_modal(f, size, "Session Complete! 🎯\nR: Review • Ctrl+Q: Quit", "Done"),
-        Screen::ConfirmQuit => draw_modal(f, size, "Are you sure you want to exit? (Y/N)", "Confirm Exit"),
+        Screen::Done => {
+            let summary = app
+                .eng
+                .as_ref()
+                .map(|eng| {
+                    eng.rating_counts()
+                        .iter()
+                        .map(|(r, n)| format!("{}: {}", r.label(), n))
+                        .collect::<Vec<_>>()
+                        .join(" • ")
+                })
+                .unwrap_or_default();
+            let msg = format!(
+                "Session Complete! 🎯\n\n{summary}\n\n{}: Review • {}: Quit",
+                keymap.keys(Scope::Done, Action::Review),
+                keymap.keys(Scope::Global, Action::Quit)
+            );
+            draw_modal(f, size, &msg, "Done")
+        }
+        Screen::ConfirmQuit => {
+            let msg = format!(
+                "Are you sure you want to exit? ({}/{})",
+                keymap.keys(Scope::ConfirmQuit, Action::Yes),
+                keymap.keys(Scope::ConfirmQuit, Action::No)
+            );
+            draw_modal(f, size, &msg, "Confirm Exit")
+        }
+        Screen::Unsaved => {
+            let topic = app
+                .current_topic
+                .as_ref()
+                .map_or("this topic", |t| t.title.as_str());
+            let msg = format!(
+                "{topic} has unsaved changes.\n\n{}: Save • {}: Discard • {}: Cancel",
+                keymap.keys(Scope::Unsaved, Action::Save),
+                keymap.keys(Scope::Unsaved, Action::Discard),
+                keymap.keys(Scope::Unsaved, Action::Cancel)
+            );
+            draw_modal(f, size, &msg, "Unsaved Changes")
+        }
     }
 
     let (pct, cur, total) = if let Some(eng) = &app.eng {
@@ -772,7 +2362,7 @@ fn ui(f: &mut ratatui::Frame, app: &mut App) {
         .block(Block::default().borders(Borders::ALL).title("Progress"))
         .gauge_style(
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme.accent)
                 .bg(Color::Black)
                 .add_modifier(Modifier::BOLD),
         )
@@ -783,42 +2373,239 @@ fn ui(f: &mut ratatui::Frame, app: &mut App) {
         ));
     f.render_widget(gauge, layout[3]);
 
-    if app.screen == Screen::Ask
-        || app.screen == Screen::TopicCreate
-        || matches!(app.screen, Screen::EditQuestion | Screen::EditAnswer)
-    {
-        let title = if app.screen == Screen::TopicCreate {
-            "Topic Name"
-        } else {
-            "Input"
+    if matches!(
+        app.screen,
+        Screen::Ask | Screen::TopicName | Screen::Search | Screen::FindCard | Screen::EditTags
+    ) {
+        let title = match app.screen {
+            Screen::TopicName => "Topic Name".to_string(),
+            Screen::Search => "Search".to_string(),
+            Screen::FindCard => "Find".to_string(),
+            Screen::EditTags => vim_title(app, "Tags (separated by spaces or commas)"),
+            _ => vim_title(app, "Input"),
         };
-        let input = Paragraph::new(app.input.as_str())
+        // One line that scrolls sideways to keep the cursor in view.
+        let width = layout[2].width.saturating_sub(2);
+        let scroll_x = app.input.scroll_x(width);
+        let (_, col) = app.input.line_col();
+        // Notices take the place of the title until the next key press.
+        let title = match &app.notice {
+            Some(notice) => Span::styled(notice.as_str(), Style::default().fg(theme.notice)),
+            None => Span::raw(title),
+        };
+        let input = Paragraph::new(app.input.text())
             .block(Block::default().borders(Borders::ALL).title(title))
-            .wrap(Wrap { trim: false });
+            .scroll((0, scroll_x));
         f.render_widget(input, layout[2]);
-        f.set_cursor(layout[2].x + app.cursor as u16 + 1, layout[2].y + 1);
+        f.set_cursor(layout[2].x + 1 + col as u16 - scroll_x, layout[2].y + 1);
     } else {
-        let hint_text = match app.screen {
-            Screen::TopicSelect => "Up/Down: select • Enter: open • C: create • Ctrl+Q: quit",
-            Screen::CardList => "Up/Down: select • E: edit question • A: edit answer • N: add • D: delete • S: save • B: back",
-            Screen::Reveal => "Ctrl+Q: Quit • N: Next • R: Review • Ctrl+E/A: Edit • Ctrl+S: Save",
-            _ => "Ctrl+Q: Quit • Ctrl+R: Review • Ctrl+S: Save",
+        let mut hint_text = keymap.hint(app.screen.scope());
+        if app.screen.scope() == Scope::Editor {
+            hint_text.insert_str(0, "Enter: New line • ");
+        }
+        let hint = if let Some(notice) = &app.notice {
+            Paragraph::new(notice.as_str())
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .style(Style::default().fg(theme.notice))
+        } else {
+            Paragraph::new(hint_text)
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .style(Style::default().fg(theme.hint))
         };
-        let hint = Paragraph::new(hint_text)
-            .alignment(Alignment::Center)
-            .style(Style::default().fg(Color::DarkGray));
         f.render_widget(hint, layout[2]);
     }
+
+    if app.show_help {
+        draw_help(f, size, app);
+    }
+}
+
+// The cards of the open topic next to the answer of the selected one.
+fn draw_card_list(f: &mut ratatui::Frame, area: Rect, app: &App, state: &mut ListState) {
+    let Some(eng) = &app.eng else {
+        return;
+    };
+    let theme = &app.config.theme;
+    let visible = app.visible_cards();
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(area);
+
+    let mut title = "Edit Cards".to_string();
+    let mut narrowed = Vec::new();
+    if !eng.filter.is_empty() {
+        narrowed.push(eng.filter.summary());
+    }
+    if !app.card_query.trim().is_empty() {
+        narrowed.push(format!("{:?}", app.card_query.trim()));
+    }
+    if !narrowed.is_empty() {
+        title.push_str(&format!(
+            " ({} • {} of {} cards)",
+            narrowed.join(" "),
+            visible.len(),
+            eng.cards.len()
+        ));
+    }
+
+    let terms = search::terms(&app.card_query);
+    let mark = Style::default()
+        .fg(theme.accent)
+        .add_modifier(Modifier::UNDERLINED);
+    let items: Vec<ListItem> = visible
+        .iter()
+        .map(|&i| {
+            let c = &eng.cards[i];
+            let from = eng.subtopic(i).map_or(String::new(), |t| format!("[{t}] "));
+            let mut spans = vec![Span::raw(format!("Card {}: {from}", i + 1))];
+            let question = first_line(&c.question);
+            for (s, hit) in search::highlight(&question, &terms) {
+                let style = if hit { mark } else { Style::default() };
+                spans.push(Span::styled(s.to_string(), style));
+            }
+            if !c.tags.is_empty() {
+                let tags = c.tags.iter().map(|t| format!(" #{t}")).collect::<String>();
+                spans.push(Span::styled(tags, Style::default().fg(theme.hint)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(
+            Style::default()
+                .fg(theme.selected)
+                .add_modifier(Modifier::BOLD),
+        );
+    state.select(visible.iter().position(|&i| i == app.selected_card));
+    f.render_stateful_widget(list, columns[0], state);
+
+    let mut preview = Vec::new();
+    if let Some(c) = state.selected().map(|pos| &eng.cards[visible[pos]]) {
+        preview.extend(c.answer.lines().map(|l| Line::from(l.to_string())));
+        if !c.notes.is_empty() {
+            preview.push(Line::from(""));
+            preview.extend(
+                c.notes
+                    .lines()
+                    .map(|l| Line::styled(l.to_string(), Style::default().fg(theme.hint))),
+            );
+        }
+    }
+    let preview = Paragraph::new(preview)
+        .block(Block::default().borders(Borders::ALL).title("Answer"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(preview, columns[1]);
+}
+
+// Two lines per match: where the card is with its question, then its answer,
+// with the search terms highlighted.
+fn draw_search(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let theme = &app.config.theme;
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Search All Topics");
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let terms = search::terms(app.input.text());
+    let hint = Style::default().fg(theme.hint);
+    let mark = Style::default()
+        .fg(theme.accent)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let highlighted = |text: &str, base: Style| -> Vec<Span<'static>> {
+        // The line with the first match, so that it is visible.
+        let line = text
+            .lines()
+            .find(|l| terms.iter().any(|t| !search::find_all(l, t).is_empty()))
+            .or(text.lines().next())
+            .unwrap_or("");
+        search::highlight(line, &terms)
+            .into_iter()
+            .map(|(s, hit)| Span::styled(s.to_string(), if hit { mark } else { base }))
+            .collect()
+    };
+
+    let mut lines = vec![
+        Line::styled(
+            match (terms.is_empty(), app.search_hits.len()) {
+                (true, _) => "Type to search the questions and answers of every topic.".into(),
+                (false, 0) => "No matches.".into(),
+                (false, n) => format!("{n} match(es)"),
+            },
+            hint,
+        ),
+        Line::from(""),
+    ];
+    let per_page = (inner.height.saturating_sub(2) / 2).max(1) as usize;
+    let first = (app.selected_hit + 1).saturating_sub(per_page);
+    for (i, hit) in app
+        .search_hits
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(per_page)
+    {
+        let base = if i == app.selected_hit {
+            Style::default()
+                .fg(theme.selected)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let topic = app.topic_path(&app.topics[hit.topic]);
+        let mut question = vec![Span::styled(
+            format!("{topic} • Card {}: ", hit.number),
+            base,
+        )];
+        question.extend(highlighted(&hit.card.question, base));
+        lines.push(Line::from(question));
+        let mut answer = vec![Span::raw("    ")];
+        answer.extend(highlighted(&hit.card.answer, hint));
+        lines.push(Line::from(answer));
+    }
+    f.render_widget(Paragraph::new(lines), inner);
 }
 
 fn draw_ask(f: &mut ratatui::Frame, area: Rect, app: &App) {
-    let block = Block::default().borders(Borders::ALL).title("Question");
+    let reversed = app.eng.as_ref().is_some_and(|e| {
+        e.current_card()
+            .is_some_and(|(i, _, _)| e.reversed.contains(&i))
+    });
+    let title = if reversed {
+        "Answer (give the question)"
+    } else {
+        "Question"
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
     let inner = block.inner(area);
     f.render_widget(block, area);
     if let Some(eng) = &app.eng {
-        if let Some((_i, q, _a)) = eng.current_card() {
-            let text = Paragraph::new(q)
-                .wrap(Wrap { trim: true })
+        if let Some((i, q, _a)) = eng.current_card() {
+            let mut lines: Vec<Line> = q.lines().map(Line::from).collect();
+            for (n, option) in eng.choices.get(&i).into_iter().flatten().enumerate() {
+                let style = if n == app.selected_choice {
+                    Style::default()
+                        .fg(app.config.theme.selected)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                lines.push(Line::from(""));
+                for (k, text) in option.lines().enumerate() {
+                    let prefix = if k == 0 {
+                        format!("{}. ", n + 1)
+                    } else {
+                        "   ".to_string()
+                    };
+                    lines.push(Line::styled(format!("{prefix}{text}"), style));
+                }
+            }
+            let text = Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
                 .alignment(Alignment::Left);
             f.render_widget(text, inner);
         }
@@ -826,35 +2613,75 @@ fn draw_ask(f: &mut ratatui::Frame, area: Rect, app: &App) {
 }
 
 fn draw_reveal(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let theme = &app.config.theme;
     let block = Block::default().borders(Borders::ALL).title("Answer");
     let inner = block.inner(area);
     f.render_widget(block, area);
     if let Some(eng) = &app.eng {
-        if let Some((_i, q, a)) = eng.current_card() {
-            let lines = vec![
-                Line::from(vec![
-                    Span::styled("Question: ", Style::default().add_modifier(Modifier::BOLD)),
-                    Span::raw(q),
-                ]),
-                Line::from(""),
-                Line::from(vec![
+        if let Some((i, q, a)) = eng.current_card() {
+            let resp = eng.responses.get(&i).map(|s| s.as_str()).unwrap_or("");
+            let verdict = match eng.grades.get(&i) {
+                Some(g) => {
+                    let color = match g {
+                        Grade::Correct => theme.correct,
+                        Grade::Close => theme.close,
+                        Grade::Wrong => theme.wrong,
+                    };
                     Span::styled(
-                        "Answer: ",
-                        Style::default()
-                            .fg(Color::Green)
-                            .add_modifier(Modifier::BOLD),
-                    ),
-                    Span::raw(a),
-                ]),
-                Line::from(""),
-                Line::from("Press N: next • E: edit question • A: edit answer"),
-            ];
-            let para = Paragraph::new(lines).wrap(Wrap { trim: true });
+                        format!("  [{}]", g.label()),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    )
+                }
+                None => Span::raw(""),
+            };
+            let bold = Style::default().add_modifier(Modifier::BOLD);
+            let (prompt, expected) = if eng.reversed.contains(&i) {
+                ("Answer: ", "Question: ")
+            } else {
+                ("Question: ", "Answer: ")
+            };
+            let mut lines = labeled_lines(prompt, bold, q);
+            lines.push(Line::from(""));
+            let mut you = labeled_lines("You: ", bold, resp);
+            if let Some(last) = you.last_mut() {
+                last.spans.push(verdict);
+            }
+            lines.extend(you);
+            lines.push(Line::from(""));
+            lines.extend(labeled_lines(expected, bold.fg(theme.correct), a));
+            lines.push(Line::from(""));
+            let keymap = &app.config.keymap;
+            let rate = [
+                Action::RateAgain,
+                Action::RateHard,
+                Action::RateGood,
+                Action::RateEasy,
+            ]
+            .map(|a| format!("{}: {}", keymap.keys(Scope::Reveal, a), a.label()))
+            .join(" • ");
+            // The rating Enter/N records, derived from the automatic grade.
+            lines.push(Line::from(format!(
+                "Rate {rate} • {}: accept suggested ({})",
+                keymap.keys(Scope::Reveal, Action::NextCard),
+                eng.suggested_rating(i).label()
+            )));
+            let para = Paragraph::new(lines).wrap(Wrap { trim: false });
             f.render_widget(para, inner);
         }
     }
 }
 
+// Puts a styled label in front of the first line of a possibly multi-line
+// text, keeping the remaining lines as they are.
+fn labeled_lines<'a>(label: &'a str, style: Style, text: &'a str) -> Vec<Line<'a>> {
+    let mut rest = text.lines();
+    let first = Line::from(vec![
+        Span::styled(label, style),
+        Span::raw(rest.next().unwrap_or("")),
+    ]);
+    std::iter::once(first).chain(rest.map(Line::from)).collect()
+}
+
 fn draw_review(f: &mut ratatui::Frame, area: Rect, app: &App) {
     let block = Block::default().borders(Borders::ALL).title("Review");
     let inner = block.inner(area);
@@ -863,14 +2690,22 @@ fn draw_review(f: &mut ratatui::Frame, area: Rect, app: &App) {
         eng.responses
             .keys()
             .map(|i| {
-                let q = &eng.questions[*i];
-                let a = &eng.answers[*i];
+                let (q, a) = eng.sides(*i);
                 let y = eng.responses.get(i).map(|s| s.as_str()).unwrap_or("(none)");
+                let g = eng.grades.get(i).map(|g| g.label()).unwrap_or("-");
+                let r = eng.ratings.get(i).map(|r| r.label()).unwrap_or("-");
+                let reversed = if eng.reversed.contains(i) {
+                    " (reversed)"
+                } else {
+                    ""
+                };
                 format!(
-                    "Q#{}\n{}\n\nYou: {}\nCorrect: {}\n{}\n",
+                    "Q#{}{reversed}\n{}\n\nYou: {} ({}, rated {})\nCorrect: {}\n{}\n",
                     i + 1,
                     q,
                     y,
+                    g,
+                    r,
                     a,
                     "-".repeat(40)
                 )
@@ -885,35 +2720,324 @@ fn draw_review(f: &mut ratatui::Frame, area: Rect, app: &App) {
     f.render_widget(para, inner);
 }
 
-fn draw_editor(f: &mut ratatui::Frame, area: Rect, app: &App, editing_question: bool) {
-    let title = if editing_question {
-        "Edit Question"
+fn draw_dashboard(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let Some(dash) = &app.dashboard else {
+        return;
+    };
+    let theme = &app.config.theme;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[0]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    let max_x = dash.accuracy.last().map(|p| p.0).unwrap_or(0.0).max(1.0);
+    let date_label = |d: Option<NaiveDate>| {
+        d.map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+    let dataset = Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(theme.accent))
+        .data(&dash.accuracy);
+    let chart = Chart::new(vec![dataset])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Accuracy over time (%)"),
+        )
+        .x_axis(Axis::default().bounds([0.0, max_x]).labels(vec![
+            Span::raw(date_label(dash.first_day)),
+            Span::raw(date_label(dash.last_day)),
+        ]))
+        .y_axis(Axis::default().bounds([0.0, 100.0]).labels(vec![
+            Span::raw("0"),
+            Span::raw("50"),
+            Span::raw("100"),
+        ]));
+    f.render_widget(chart, top[0]);
+
+    let bars: Vec<Bar> = dash
+        .per_day
+        .iter()
+        .map(|(label, n)| Bar::default().label(Line::from(label.as_str())).value(*n))
+        .collect();
+    let per_day = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Cards studied per day (last 14 days)"),
+        )
+        .data(BarGroup::default().bars(&bars))
+        .bar_width(3)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(theme.selected));
+    f.render_widget(per_day, top[1]);
+
+    let hardest: Vec<Line> = if dash.hardest.is_empty() {
+        vec![Line::from("No cards studied yet.")]
     } else {
-        "Edit Answer"
+        dash.hardest
+            .iter()
+            .map(|(q, acc, seen)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>4.0}% ", acc * 100.0),
+                        Style::default().fg(theme.wrong),
+                    ),
+                    Span::styled(format!("({seen}×) "), Style::default().fg(theme.hint)),
+                    Span::raw(q.as_str()),
+                ])
+            })
+            .collect()
+    };
+    let hardest = Paragraph::new(hardest).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Hardest cards"),
+    );
+    f.render_widget(hardest, bottom[0]);
+
+    let bars: Vec<Bar> = dash
+        .mastery
+        .iter()
+        .map(|(topic, pct)| {
+            Bar::default()
+                .label(Line::from(topic.as_str()))
+                .value(*pct)
+                .text_value(format!("{pct}%"))
+        })
+        .collect();
+    let mastery = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Topic mastery (mature cards)"),
+        )
+        .direction(Direction::Horizontal)
+        .data(BarGroup::default().bars(&bars))
+        .max(100)
+        .bar_width(1)
+        .bar_gap(0)
+        .bar_style(Style::default().fg(theme.correct));
+    f.render_widget(mastery, bottom[1]);
+}
+
+fn draw_card_stats(f: &mut ratatui::Frame, area: Rect, app: &App) {
+    let Some(eng) = &app.eng else {
+        return;
     };
+    let header = Row::new(vec![
+        "#",
+        "Question",
+        "Seen",
+        "Correct",
+        "Acc",
+        "Streak",
+        "Avg time",
+        "Last seen",
+        "Reverse",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows: Vec<Row> = eng
+        .cards
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let st = eng.stats.get(&c.id).cloned().unwrap_or_default();
+            let (acc, avg, last) = if st.seen == 0 {
+                ("-".to_string(), "-".to_string(), "never".to_string())
+            } else {
+                (
+                    format!("{:.0}%", st.accuracy() * 100.0),
+                    format!("{:.1}s", st.avg_response_ms as f64 / 1000.0),
+                    st.last_seen
+                        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default(),
+                )
+            };
+            let reverse = match eng.stats.get_reverse(&c.id).filter(|st| st.seen > 0) {
+                Some(st) => format!("{:.0}% of {}", st.accuracy() * 100.0, st.seen),
+                None => "-".to_string(),
+            };
+            Row::new(vec![
+                (i + 1).to_string(),
+                first_line(&c.question),
+                st.seen.to_string(),
+                st.correct.to_string(),
+                acc,
+                st.streak.to_string(),
+                avg,
+                last,
+                reverse,
+            ])
+        })
+        .collect();
+    let widths = [
+        Constraint::Length(4),
+        Constraint::Min(20),
+        Constraint::Length(5),
+        Constraint::Length(8),
+        Constraint::Length(5),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(17),
+        Constraint::Length(11),
+    ];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Card Statistics"),
+    );
+    let mut state = TableState::default().with_offset(app.review_scroll as usize);
+    f.render_stateful_widget(table, area, &mut state);
+}
+
+fn draw_editor(f: &mut ratatui::Frame, area: Rect, app: &App, editing_question: bool) {
+    let title = vim_title(
+        app,
+        if editing_question {
+            "Edit Question"
+        } else {
+            "Edit Answer"
+        },
+    );
     let block = Block::default().borders(Borders::ALL).title(title);
     let inner = block.inner(area);
     f.render_widget(block, area);
-    let p = Paragraph::new(app.input.as_str())
-        .wrap(Wrap { trim: false })
+    let (row, col) = app.input.line_col();
+    let scroll_y = (row as u16).saturating_sub(inner.height.saturating_sub(1));
+    let scroll_x = (col as u16).saturating_sub(inner.width.saturating_sub(1));
+    let p = Paragraph::new(app.input.text())
+        .scroll((scroll_y, scroll_x))
         .alignment(Alignment::Left);
     f.render_widget(p, inner);
-    let hint = Paragraph::new("Enter: Save • Esc: Cancel • Ctrl+S: Save to file")
-        .alignment(Alignment::Center)
-        .style(Style::default().fg(Color::DarkGray));
+    f.set_cursor(
+        inner.x + col as u16 - scroll_x,
+        inner.y + row as u16 - scroll_y,
+    );
+}
+
+// The topic tree, with `dimmed` greyed out (the source topic when merging).
+fn topic_lines(app: &App, dimmed: Option<&Topic>) -> Vec<Line<'static>> {
+    let theme = &app.config.theme;
+    app.visible_topics()
+        .into_iter()
+        .map(|i| {
+            let t = &app.topics[i];
+            let style = if i == app.selected_topic {
+                Style::default()
+                    .fg(theme.selected)
+                    .add_modifier(Modifier::BOLD)
+            } else if dimmed == Some(t) {
+                Style::default().fg(theme.hint)
+            } else {
+                Style::default()
+            };
+            let marker = if !app.has_subtopics(i) {
+                "  "
+            } else if app.collapsed.contains(&t.dir) {
+                "▸ "
+            } else {
+                "▾ "
+            };
+            Line::from(Span::styled(
+                format!("{}{marker}{}", "  ".repeat(t.depth()), t.title),
+                style,
+            ))
+        })
+        .collect()
+}
+
+fn vim_title(app: &App, title: &str) -> String {
+    if app.config.vim {
+        format!("{title} -- {} --", app.vim.mode.label())
+    } else {
+        title.to_string()
+    }
+}
+
+// Lists the bindings of a scope one per line, e.g. "S: Start quiz".
+fn key_menu(app: &App, scope: Scope, label: impl Fn(Action) -> String) -> String {
+    app.config
+        .keymap
+        .bindings(scope)
+        .iter()
+        .filter(|(_, keys)| !keys.is_empty())
+        .map(|(a, keys)| format!("{}: {}", join_keys(keys), label(*a)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn draw_help(f: &mut ratatui::Frame, size: Rect, app: &App) {
+    let scope = app.screen.scope();
+    let keymap = &app.config.keymap;
+    let mut lines = Vec::new();
+    for s in [scope, Scope::Global] {
+        lines.push(Line::from(Span::styled(
+            s.label(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        for (action, keys) in keymap.bindings(s) {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {:<22}", join_keys(keys)),
+                    Style::default().fg(app.config.theme.selected),
+                ),
+                Span::raw(action.label()),
+            ]));
+        }
+        lines.push(Line::from(""));
+    }
+    if scope.takes_text() {
+        lines.push(Line::from(
+            "Other keys edit the text: arrows and Home/End move, Ctrl+←/→ by word, \
+             Ctrl+W/Ctrl+U delete a word/to line start, Ctrl+Z/Ctrl+Y undo/redo; \
+             pasting works too.",
+        ));
+    }
+    if app.config.vim {
+        lines.push(Line::from(
+            "Vim: j/k gg G in lists • Esc normal mode • i a I A o O insert • \
+             h j k l w b e 0 $ gg G move • x dd delete • u undo",
+        ));
+    }
+    lines.push(Line::from(Span::styled(
+        "Press any key to close",
+        Style::default().fg(app.config.theme.hint),
+    )));
+    let area = centered_rect(70, 80, size);
+    let block = Block::default().borders(Borders::ALL).title("Keys");
+    f.render_widget(Clear, area);
     f.render_widget(
-        hint,
-        Rect {
-            x: area.x,
-            y: area.y + area.height.saturating_sub(2),
-            width: area.width,
-            height: 1,
-        },
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
     );
 }
 
 fn draw_modal(f: &mut ratatui::Frame, size: Rect, msg: &str, title: &str) {
     let area = centered_rect(60, 30, size);
+    // Taller if the message does not fit.
+    let height = (msg.lines().count() as u16 + 2).min(size.height);
+    let area = if height > area.height {
+        Rect {
+            y: size.y + (size.height - height) / 2,
+            height,
+            ..area
+        }
+    } else {
+        area
+    };
     let block = Block::default().borders(Borders::ALL).title(title);
     let p = Paragraph::new(msg)
         .block(block)
@@ -923,6 +3047,16 @@ fn draw_modal(f: &mut ratatui::Frame, size: Rect, msg: &str, title: &str) {
     f.render_widget(p, area);
 }
 
+fn first_line(text: &str) -> String {
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or("");
+    if lines.next().is_some() {
+        format!("{first} …")
+    } else {
+        first.to_string()
+    }
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let v = Layout::default()
         .direction(Direction::Vertical)
@@ -942,3 +3076,147 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(v[1]);
     h[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(name: &str, questions: &[&str]) -> FlashCardEngine {
+        let dir = std::env::temp_dir().join(format!(
+            "flashcards-engine-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        let mut eng = FlashCardEngine::from_dir(&dir).unwrap();
+        eng.append_cards(
+            questions
+                .iter()
+                .map(|q| Card::new(q.to_string(), format!("{q}!")))
+                .collect(),
+        );
+        eng
+    }
+
+    fn question(eng: &FlashCardEngine, idx: usize) -> &str {
+        &eng.cards[idx].question
+    }
+
+    fn app(name: &str, questions: &[&str]) -> App {
+        let dir =
+            std::env::temp_dir().join(format!("flashcards-app-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let paths = config::Paths {
+            topics_dir: dir.join("topics"),
+            sessions_dir: dir.join("sessions"),
+            trash_dir: dir.join("trash"),
+            exports_dir: dir.join("exports"),
+        };
+        let mut app = App::new(paths, Config::default());
+        let topic = topics::create(&app.topics_dir, None, "Test").unwrap();
+        app.load_topics().unwrap();
+        app.load_eng(&topic).unwrap();
+        let eng = app.eng.as_mut().unwrap();
+        for q in questions {
+            eng.push_card(Vec::new());
+            let idx = eng.cards.len() - 1;
+            eng.set_question(idx, q.to_string());
+        }
+        app
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for key in keys.split_whitespace() {
+            let spec: keymap::KeySpec = key.parse().unwrap();
+            assert!(!handle(app, KeyEvent::new(spec.code, spec.modifiers)).unwrap());
+        }
+    }
+
+    fn remove_app(app: App) {
+        let dir = app.topics_dir.parent().unwrap().to_path_buf();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn edits_mid_session_follow_the_cards() {
+        let mut eng = engine("session", &["a", "b", "c", "d"]);
+        eng.set_random(false);
+        eng.reversed.insert(2);
+        eng.record(0, "a!".into()).unwrap();
+        eng.next();
+        eng.record(1, "wrong".into()).unwrap();
+        eng.next();
+        assert_eq!(eng.current_card().map(|c| c.1), Some("c!"));
+
+        eng.move_card(0, 3);
+        eng.remove_card(0);
+        let (idx, prompt, _) = eng.current_card().unwrap();
+        assert_eq!((question(&eng, idx), prompt), ("c", "c!"));
+        assert_eq!(eng.current, 1);
+        let answered: Vec<_> = eng
+            .responses
+            .iter()
+            .map(|(&i, r)| (question(&eng, i), r.as_str()))
+            .collect();
+        assert_eq!(answered, [("a", "a!")]);
+        assert!(eng.reversed.iter().all(|&i| question(&eng, i) == "c"));
+        let order: Vec<_> = eng.order.iter().map(|&i| question(&eng, i)).collect();
+        assert_eq!(order, ["a", "c", "d"]);
+
+        // The deleted card is back in the deck but not in this session.
+        eng.undo();
+        eng.undo();
+        assert_eq!(eng.current_card().map(|c| c.0), Some(2));
+        assert_eq!(eng.order, [0, 2, 3]);
+        assert_eq!(eng.responses.keys().copied().collect::<Vec<_>>(), [0]);
+        assert_eq!(eng.reversed, BTreeSet::from([2]));
+        fs::remove_dir_all(&eng.decks[0].dir).unwrap();
+    }
+
+    #[test]
+    fn edits_before_a_session_keep_the_filter() {
+        let mut eng = engine("filter", &["a", "b"]);
+        eng.cards[1].tags = vec!["x".into()];
+        eng.filter.include.insert("x".into());
+        let idx = eng.push_card(vec!["x".into()]);
+        assert_eq!(eng.order, [1, idx]);
+        eng.remove_card(1);
+        assert_eq!(eng.order, [1]);
+        fs::remove_dir_all(&eng.decks[0].dir).unwrap();
+    }
+
+    #[test]
+    fn adding_cards_in_the_editor_keeps_progress_in_range() {
+        let mut app = app("add", &[]);
+        app.in_edit_mode = true;
+        app.screen = Screen::CardList;
+        for q in ["a", "b", "c", "d"] {
+            press(&mut app, &format!("n {q} ctrl+d"));
+        }
+        let eng = app.eng.as_ref().unwrap();
+        let questions: Vec<_> = (0..4).map(|i| question(eng, i)).collect();
+        assert_eq!(questions, ["a", "b", "c", "d"]);
+        assert_eq!((eng.current, eng.order.clone()), (0, vec![0, 1, 2, 3]));
+        assert_eq!(eng.progress(), 0.0);
+        remove_app(app);
+    }
+
+    #[test]
+    fn editing_from_the_answer_changes_the_studied_card() {
+        let mut app = app("reveal", &["a", "b", "c", "d"]);
+        let eng = app.eng.as_mut().unwrap();
+        eng.set_random(false);
+        eng.order.reverse();
+        eng.next();
+        let (idx, _, _) = eng.current_card().unwrap();
+        assert_eq!(question(eng, idx), "c");
+        app.screen = Screen::Reveal;
+        press(&mut app, "ctrl+e x ctrl+d");
+        assert!(app.screen == Screen::Reveal);
+        let eng = app.eng.as_ref().unwrap();
+        let questions: Vec<_> = (0..4).map(|i| question(eng, i)).collect();
+        assert_eq!(questions, ["a", "b", "cx", "d"]);
+        assert_eq!(eng.current_card().map(|c| c.0), Some(idx));
+        remove_app(app);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{deck, deck::Card, topics::Topic};
+use anyhow::Result;
+use std::{cmp::Reverse, ops::Range, path::Path};
+
+// Full-text search over the questions and answers of every topic.
+
+pub struct Hit {
+    // Index into the topics the search ran over.
+    pub topic: usize,
+    pub card: Card,
+    // 1-based position of the card in its topic's own deck.
+    pub number: usize,
+    pub score: u32,
+}
+
+// The cards of each topic, loaded once when the search screen opens so that
+// typing does not hit the disk.
+pub fn index(topics_dir: &Path, topics: &[Topic]) -> Result<Vec<Vec<Card>>> {
+    topics
+        .iter()
+        .map(|t| deck::load(&topics_dir.join(&t.dir)))
+        .collect()
+}
+
+pub fn terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+// Cards containing every term, best first. Matches in the question count
+// more than in the answer, whole words more than parts of words, and the
+// query as typed more than its terms scattered.
+pub fn run(index: &[Vec<Card>], query: &str) -> Vec<Hit> {
+    let terms = terms(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let phrase = query.trim().to_lowercase();
+    let mut hits = Vec::new();
+    for (topic, cards) in index.iter().enumerate() {
+        for (i, card) in cards.iter().enumerate() {
+            let mut score = 0;
+            for term in &terms {
+                let q = term_score(&card.question, term);
+                let a = term_score(&card.answer, term);
+                if q + a == 0 {
+                    score = 0;
+                    break;
+                }
+                score += 3 * q + a;
+            }
+            if score == 0 {
+                continue;
+            }
+            if terms.len() > 1 && contains(&card.question, &phrase) {
+                score += 10;
+            }
+            hits.push(Hit {
+                topic,
+                card: card.clone(),
+                number: i + 1,
+                score,
+            });
+        }
+    }
+    // Stable, so equal scores stay in topic order.
+    hits.sort_by_key(|h| Reverse(h.score));
+    hits
+}
+
+fn term_score(text: &str, term: &str) -> u32 {
+    find_all(text, term)
+        .into_iter()
+        .map(|r| {
+            let before = text[..r.start].chars().next_back();
+            let after = text[r.end..].chars().next();
+            if before.is_some_and(char::is_alphanumeric) || after.is_some_and(char::is_alphanumeric)
+            {
+                1
+            } else {
+                2
+            }
+        })
+        .sum()
+}
+
+// Byte ranges of the places `term` (lowercase) occurs in `text`, ignoring
+// case. Ranges do not overlap.
+pub fn find_all(text: &str, term: &str) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    if term.is_empty() {
+        return found;
+    }
+    let mut from = 0;
+    for (start, _) in text.char_indices() {
+        if start < from {
+            continue;
+        }
+        if let Some(len) = match_len(&text[start..], term) {
+            found.push(start..start + len);
+            from = start + len;
+        }
+    }
+    found
+}
+
+// Length in bytes of the prefix of `text` that matches `term`, if any.
+fn match_len(text: &str, term: &str) -> Option<usize> {
+    let mut wanted = term.chars();
+    let mut next = wanted.next();
+    for (i, c) in text.char_indices() {
+        for lower in c.to_lowercase() {
+            if next != Some(lower) {
+                return None;
+            }
+            next = wanted.next();
+        }
+        if next.is_none() {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+pub fn contains(text: &str, term: &str) -> bool {
+    !find_all(text, term).is_empty()
+}
+
+// Splits `text` into pieces, each marked whether it matches one of the terms.
+pub fn highlight<'a>(text: &'a str, terms: &[String]) -> Vec<(&'a str, bool)> {
+    let mut ranges: Vec<Range<usize>> = terms.iter().flat_map(|t| find_all(text, t)).collect();
+    ranges.sort_by_key(|r| r.start);
+    let mut pieces = Vec::new();
+    let mut pos = 0;
+    for r in ranges {
+        if r.end <= pos {
+            continue;
+        }
+        let start = r.start.max(pos);
+        if start > pos {
+            pieces.push((&text[pos..start], false));
+        }
+        pieces.push((&text[start..r.end], true));
+        pos = r.end;
+    }
+    if pos < text.len() {
+        pieces.push((&text[pos..], false));
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(pairs: &[(&str, &str)]) -> Vec<Card> {
+        pairs
+            .iter()
+            .map(|(q, a)| Card::new(q.to_string(), a.to_string()))
+            .collect()
+    }
+
+    fn questions(hits: &[Hit]) -> Vec<&str> {
+        hits.iter().map(|h| h.card.question.as_str()).collect()
+    }
+
+    #[test]
+    fn question_and_whole_word_matches_rank_first() {
+        let index = [
+            cards(&[("Trust issues", "rusty"), ("What is Rust?", "A language")]),
+            cards(&[("Language", "Rust is one")]),
+        ];
+        let hits = run(&index, "rust");
+        assert_eq!(
+            questions(&hits),
+            ["What is Rust?", "Trust issues", "Language"]
+        );
+        assert_eq!((hits[0].topic, hits[0].number), (0, 2));
+        assert_eq!((hits[2].topic, hits[2].number), (1, 1));
+        assert!(hits[0].score > hits[1].score && hits[1].score > hits[2].score);
+    }
+
+    #[test]
+    fn every_term_must_match_and_the_phrase_counts_extra() {
+        let index = [cards(&[
+            ("Language of rust", "-"),
+            ("Rust", "-"),
+            ("Rust language", "-"),
+        ])];
+        assert_eq!(
+            questions(&run(&index, "rust language")),
+            ["Rust language", "Language of rust"]
+        );
+        assert!(run(&index, "   ").is_empty());
+        assert!(run(&index, "rust python").is_empty());
+    }
+
+    #[test]
+    fn equal_scores_keep_topic_order() {
+        let index = [cards(&[("b rust", "-")]), cards(&[("a rust", "-")])];
+        assert_eq!(questions(&run(&index, "rust")), ["b rust", "a rust"]);
+    }
+
+    #[test]
+    fn find_all_ignores_case_beyond_ascii() {
+        assert_eq!(find_all("Ärger über ÄRGER", "ärger"), [0..6, 13..19]);
+        assert_eq!(find_all("日本語の本", "本"), [3..6, 12..15]);
+        assert_eq!(find_all("İstanbul", "i\u{307}stanbul"), vec![0..9]);
+        assert_eq!(find_all("İstanbul", "istanbul"), []);
+        assert_eq!(find_all("aaaa", "aa"), [0..2, 2..4]);
+        assert_eq!(find_all("text", ""), []);
+        assert!(contains("Crème BRÛLÉE", "brûlée"));
+    }
+
+    #[test]
+    fn highlight_splits_on_char_boundaries() {
+        let terms = terms("ÜBER straße");
+        assert_eq!(
+            highlight("Über die Straße", &terms),
+            [("Über", true), (" die ", false), ("Straße", true)]
+        );
+    }
+}
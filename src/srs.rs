@@ -0,0 +1,249 @@
+use crate::deck::Card;
+use anyhow::{Context, Result};
+use chrono::{Days, NaiveDate};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+pub const SCHEDULE_FILE: &str = "schedule.txt";
+const DATE_FMT: &str = "%Y-%m-%d";
+// Interval after which a card counts as learned, as in Anki's "mature".
+const MATURE_DAYS: u32 = 21;
+
+// SM-2 state for a single card.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    pub ease: f64,
+    pub interval: u32,
+    pub reps: u32,
+    pub due: NaiveDate,
+}
+
+impl Schedule {
+    pub fn new(today: NaiveDate) -> Self {
+        Self {
+            ease: 2.5,
+            interval: 0,
+            reps: 0,
+            due: today,
+        }
+    }
+
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        self.due <= today
+    }
+
+    pub fn is_mature(&self) -> bool {
+        self.interval >= MATURE_DAYS
+    }
+
+    // quality is the SM-2 response grade, 0 (blackout) to 5 (perfect).
+    pub fn review(&mut self, quality: u8, today: NaiveDate) {
+        let q = quality.min(5) as f64;
+        if quality < 3 {
+            self.reps = 0;
+            self.interval = 1;
+        } else {
+            self.reps += 1;
+            self.interval = match self.reps {
+                1 => 1,
+                2 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u32,
+            };
+        }
+        self.ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(1.3);
+        self.due = today
+            .checked_add_days(Days::new(self.interval as u64))
+            .unwrap_or(today);
+    }
+
+    fn parse(line: &str) -> Option<(String, Self)> {
+        let mut parts = line.splitn(5, '\t');
+        let due = NaiveDate::parse_from_str(parts.next()?, DATE_FMT).ok()?;
+        let interval = parts.next()?.parse().ok()?;
+        let ease = parts.next()?.parse().ok()?;
+        let reps = parts.next()?.parse().ok()?;
+        let key = parts.next()?.to_string();
+        Some((
+            key,
+            Self {
+                ease,
+                interval,
+                reps,
+                due,
+            },
+        ))
+    }
+
+    fn to_line(self, key: &str) -> String {
+        format!(
+            "{}\t{}\t{:.2}\t{}\t{}",
+            self.due.format(DATE_FMT),
+            self.interval,
+            self.ease,
+            self.reps,
+            key
+        )
+    }
+}
+
+// Schedules are keyed by card id so they stay attached to their card when
+// cards are added, removed, edited or reordered. Files written before cards
+// had ids were keyed by question text, which is still accepted on load.
+pub fn load(path: &Path, cards: &[Card], today: NaiveDate) -> Result<Vec<Schedule>> {
+    let mut known = HashMap::new();
+    if path.exists() {
+        let f = File::open(path).with_context(|| format!("Opening {}", path.display()))?;
+        // A line that is not valid UTF-8 is skipped on its own.
+        for line in BufReader::new(f).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+                Err(e) => return Err(e).with_context(|| format!("Reading {}", path.display())),
+            };
+            if let Some((key, s)) = Schedule::parse(&line) {
+                known.insert(key, s);
+            }
+        }
+    }
+    Ok(cards
+        .iter()
+        .map(|c| {
+            known
+                .get(&c.id)
+                .or_else(|| known.get(&c.question))
+                .copied()
+                .unwrap_or_else(|| Schedule::new(today))
+        })
+        .collect())
+}
+
+pub fn save(path: &Path, cards: &[Card], schedule: &[Schedule]) -> Result<()> {
+    let text: String = cards
+        .iter()
+        .zip(schedule)
+        .map(|(c, s)| s.to_line(&c.id) + "\n")
+        .collect();
+    crate::write_atomic(path, &text)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rating {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Rating {
+    pub const ALL: [Rating; 4] = [Rating::Again, Rating::Hard, Rating::Good, Rating::Easy];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Rating::Again => "Again",
+            Rating::Hard => "Hard",
+            Rating::Good => "Good",
+            Rating::Easy => "Easy",
+        }
+    }
+
+    pub fn quality(self) -> u8 {
+        match self {
+            Rating::Again => 1,
+            Rating::Hard => 3,
+            Rating::Good => 4,
+            Rating::Easy => 5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, d).unwrap()
+    }
+
+    #[test]
+    fn intervals_follow_sm2() {
+        let mut s = Schedule::new(day(1));
+        s.review(4, day(1));
+        assert_eq!((s.reps, s.interval, s.due), (1, 1, day(2)));
+        s.review(4, day(2));
+        assert_eq!((s.reps, s.interval, s.due), (2, 6, day(8)));
+        s.review(4, day(8));
+        // Good leaves the ease at 2.5, so 6 * 2.5.
+        assert_eq!((s.reps, s.interval, s.due), (3, 15, day(23)));
+        assert_eq!(s.ease, 2.5);
+        assert!(!s.is_mature());
+        s.review(4, day(23));
+        assert!(s.is_mature());
+    }
+
+    #[test]
+    fn lapse_resets_repetitions() {
+        let mut s = Schedule::new(day(1));
+        s.review(5, day(1));
+        s.review(5, day(2));
+        assert!((s.ease - 2.7).abs() < 1e-9);
+        s.review(1, day(10));
+        assert_eq!((s.reps, s.interval, s.due), (0, 1, day(11)));
+        assert!((s.ease - 2.16).abs() < 1e-9);
+        s.review(4, day(11));
+        assert_eq!(s.interval, 1);
+    }
+
+    #[test]
+    fn ease_never_drops_below_floor() {
+        let mut s = Schedule::new(day(1));
+        for _ in 0..10 {
+            s.review(0, day(1));
+        }
+        assert_eq!(s.ease, 1.3);
+        s.review(3, day(1));
+        assert_eq!(s.ease, 1.3);
+    }
+
+    #[test]
+    fn due_today_or_earlier() {
+        let s = Schedule::new(day(5));
+        assert!(s.is_due(day(5)));
+        assert!(s.is_due(day(6)));
+        assert!(!s.is_due(day(4)));
+    }
+
+    #[test]
+    fn line_round_trip() {
+        let mut s = Schedule::new(day(1));
+        s.review(4, day(1));
+        let line = s.to_line("abc");
+        assert_eq!(Schedule::parse(&line), Some(("abc".to_string(), s)));
+        assert_eq!(Schedule::parse("not a schedule"), None);
+    }
+
+    #[test]
+    fn load_skips_unreadable_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "flashcards-srs-test-{}-schedule.txt",
+            std::process::id()
+        ));
+        let cards: Vec<Card> = ["a", "b", "c"]
+            .iter()
+            .map(|q| Card::new(q.to_string(), String::new()))
+            .collect();
+        let mut learned = Schedule::new(day(1));
+        learned.review(4, day(1));
+        let mut bytes = learned.to_line(&cards[0].id).into_bytes();
+        bytes.extend(b"\n\xff\xfe broken\ngarbage\n");
+        bytes.extend(learned.to_line(&cards[2].id).into_bytes());
+        bytes.push(b'\n');
+        std::fs::write(&path, bytes).unwrap();
+        let schedule = load(&path, &cards, day(3)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(schedule, [learned, Schedule::new(day(3)), learned]);
+    }
+}
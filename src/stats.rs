@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+pub const STATS_FILE: &str = "stats.toml";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CardStats {
+    pub seen: u32,
+    pub correct: u32,
+    pub streak: u32,
+    pub avg_response_ms: u64,
+    pub last_seen: Option<NaiveDateTime>,
+}
+
+impl CardStats {
+    pub fn record(&mut self, correct: bool, elapsed: Duration, now: NaiveDateTime) {
+        let total = self.avg_response_ms * self.seen as u64 + elapsed.as_millis() as u64;
+        self.seen += 1;
+        self.avg_response_ms = total / self.seen as u64;
+        if correct {
+            self.correct += 1;
+            self.streak += 1;
+        } else {
+            self.streak = 0;
+        }
+        self.last_seen = Some(now);
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        self.correct as f64 / self.seen.max(1) as f64
+    }
+}
+
+// Per-topic statistics keyed by card id. Answers given to the reversed card,
+// i.e. with the answer as the prompt, are counted separately.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsStore {
+    #[serde(default)]
+    cards: BTreeMap<String, CardStats>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    reverse: BTreeMap<String, CardStats>,
+}
+
+impl StatsStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Opening {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::write_atomic(path, &toml::to_string_pretty(self)?)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&CardStats> {
+        self.cards.get(id)
+    }
+
+    pub fn get_reverse(&self, id: &str) -> Option<&CardStats> {
+        self.reverse.get(id)
+    }
+
+    pub fn extend(&mut self, other: StatsStore) {
+        self.cards.extend(other.cards);
+        self.reverse.extend(other.reverse);
+    }
+
+    pub fn subset(&self, keep: impl Fn(&str) -> bool) -> StatsStore {
+        let pick = |map: &BTreeMap<String, CardStats>| {
+            map.iter()
+                .filter(|(id, _)| keep(id))
+                .map(|(id, s)| (id.clone(), s.clone()))
+                .collect()
+        };
+        StatsStore {
+            cards: pick(&self.cards),
+            reverse: pick(&self.reverse),
+        }
+    }
+
+    // Copies both directions of a card's statistics from another store.
+    pub fn copy(&mut self, from: &StatsStore, id: &str, new_id: &str) {
+        if let Some(stats) = from.cards.get(id) {
+            self.cards.insert(new_id.to_string(), stats.clone());
+        }
+        if let Some(stats) = from.reverse.get(id) {
+            self.reverse.insert(new_id.to_string(), stats.clone());
+        }
+    }
+
+    pub fn entry(&mut self, id: &str, reversed: bool) -> &mut CardStats {
+        let map = if reversed {
+            &mut self.reverse
+        } else {
+            &mut self.cards
+        };
+        map.entry(id.to_string()).or_default()
+    }
+}
+
+pub const HISTORY_FILE: &str = "history.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub finished: NaiveDateTime,
+    pub cards: u32,
+    pub correct: u32,
+}
+
+// Finished study sessions for one topic, oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default, rename = "session")]
+    pub sessions: Vec<Session>,
+}
+
+impl History {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Opening {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn append(path: &Path, session: Session) -> Result<()> {
+        let mut history = Self::load(path)?;
+        history.sessions.push(session);
+        history.save(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        crate::write_atomic(path, &toml::to_string_pretty(self)?)
+    }
+
+    // (cards, correct) totals per calendar day.
+    pub fn by_day(&self) -> BTreeMap<NaiveDate, (u32, u32)> {
+        let mut days = BTreeMap::new();
+        for s in &self.sessions {
+            let e = days.entry(s.finished.date()).or_insert((0, 0));
+            e.0 += s.cards;
+            e.1 += s.correct;
+        }
+        days
+    }
+}
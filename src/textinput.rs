@@ -0,0 +1,532 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Typing,
+    Other,
+}
+
+// Text field shared by the answer input, the topic name and the card editor.
+// The cursor is a byte offset that always sits on a grapheme boundary, so
+// accented letters, CJK and emoji are moved over and deleted as one unit.
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+    undo: Vec<(String, usize)>,
+    redo: Vec<(String, usize)>,
+    // Consecutive typing within a word is undone in one step.
+    last_edit: Option<Edit>,
+}
+
+impl TextInput {
+    pub fn new(text: String) -> Self {
+        Self {
+            cursor: text.len(),
+            text,
+            ..Self::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, pos: usize) {
+        self.cursor = pos.min(self.text.len());
+        self.last_edit = None;
+    }
+
+    pub fn take(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        *self = Self::default();
+        text
+    }
+
+    // Saves the current state as an undo step unless nothing changed since.
+    pub fn checkpoint(&mut self) {
+        self.last_edit = None;
+        if self.undo.last().is_some_and(|(t, _)| *t == self.text) {
+            return;
+        }
+        self.undo.push((self.text.clone(), self.cursor));
+        self.redo.clear();
+    }
+
+    fn begin(&mut self, edit: Edit) {
+        if edit == Edit::Other || self.last_edit != Some(Edit::Typing) {
+            self.checkpoint();
+        }
+        self.last_edit = Some(edit);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        while let Some((text, cursor)) = self.undo.pop() {
+            if text != self.text {
+                self.redo
+                    .push((std::mem::replace(&mut self.text, text), self.cursor));
+                self.cursor = cursor.min(self.text.len());
+                self.last_edit = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some((text, cursor)) = self.redo.pop() else {
+            return false;
+        };
+        self.undo
+            .push((std::mem::replace(&mut self.text, text), self.cursor));
+        self.cursor = cursor.min(self.text.len());
+        self.last_edit = None;
+        true
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let edit = if c.is_whitespace() {
+            Edit::Other
+        } else {
+            Edit::Typing
+        };
+        self.begin(edit);
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.begin(Edit::Other);
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+        self.last_edit = None;
+    }
+
+    // Replaces a byte range, leaving the cursor after the new text.
+    pub fn replace(&mut self, range: Range<usize>, with: &str) {
+        self.begin(Edit::Other);
+        self.cursor = range.start + with.len();
+        self.text.replace_range(range, with);
+        self.last_edit = None;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            let start = prev_boundary(&self.text, self.cursor);
+            self.replace(start..self.cursor, "");
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            let end = next_boundary(&self.text, self.cursor);
+            self.replace(self.cursor..end, "");
+        }
+    }
+
+    pub fn delete_word_back(&mut self) {
+        let start = word_back(&self.text, self.cursor).max(self.line_start());
+        let start = if start == self.cursor {
+            prev_boundary(&self.text, self.cursor)
+        } else {
+            start
+        };
+        self.replace(start..self.cursor, "");
+    }
+
+    pub fn delete_to_line_start(&mut self) {
+        let start = self.line_start();
+        self.replace(start..self.cursor, "");
+    }
+
+    pub fn left(&mut self) {
+        self.set_cursor(prev_boundary(&self.text, self.cursor));
+    }
+
+    pub fn right(&mut self) {
+        self.set_cursor(next_boundary(&self.text, self.cursor));
+    }
+
+    pub fn word_left(&mut self) {
+        self.set_cursor(word_back(&self.text, self.cursor));
+    }
+
+    pub fn word_right(&mut self) {
+        self.set_cursor(word_forward(&self.text, self.cursor));
+    }
+
+    pub fn line_start(&self) -> usize {
+        self.text[..self.cursor].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    pub fn line_end(&self) -> usize {
+        self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |i| self.cursor + i)
+    }
+
+    // Row and display column of the cursor.
+    pub fn line_col(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let row = before.matches('\n').count();
+        let col = before.rsplit('\n').next().unwrap_or("").width();
+        (row, col)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.text.split('\n').count()
+    }
+
+    // Moves to the given row, as close to the display column as the line
+    // allows without splitting a wide character.
+    pub fn move_to(&mut self, row: usize, col: usize) {
+        let mut offset = 0;
+        for (i, line) in self.text.split('\n').enumerate() {
+            if i == row {
+                let mut x = 0;
+                let mut pos = line.len();
+                for (b, g) in line.grapheme_indices(true) {
+                    let w = g.width();
+                    if x + w > col {
+                        pos = b;
+                        break;
+                    }
+                    x += w;
+                }
+                self.set_cursor(offset + pos);
+                return;
+            }
+            offset += line.len() + 1;
+        }
+    }
+
+    // Handles the editing keys common to every text field. Returns false for
+    // keys it does not use.
+    pub fn handle_key(&mut self, key: KeyEvent, multiline: bool) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('w') if ctrl => self.delete_word_back(),
+            KeyCode::Char('u') if ctrl => self.delete_to_line_start(),
+            KeyCode::Char('z') if ctrl => {
+                self.undo();
+            }
+            KeyCode::Char('y') if ctrl => {
+                self.redo();
+            }
+            KeyCode::Char(c) if !ctrl && !alt => self.insert_char(c),
+            KeyCode::Enter if multiline && !ctrl && !alt => self.insert_char('\n'),
+            KeyCode::Backspace if ctrl || alt => self.delete_word_back(),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left if ctrl || alt => self.word_left(),
+            KeyCode::Right if ctrl || alt => self.word_right(),
+            KeyCode::Left => self.left(),
+            KeyCode::Right => self.right(),
+            KeyCode::Home if ctrl => self.set_cursor(0),
+            KeyCode::End if ctrl => self.set_cursor(self.text.len()),
+            KeyCode::Home => self.set_cursor(self.line_start()),
+            KeyCode::End => self.set_cursor(self.line_end()),
+            KeyCode::Up if multiline => {
+                let (row, col) = self.line_col();
+                if row > 0 {
+                    self.move_to(row - 1, col);
+                }
+            }
+            KeyCode::Down if multiline => {
+                let (row, col) = self.line_col();
+                self.move_to(row + 1, col);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // Pasted text; line breaks become spaces in single-line fields.
+    pub fn paste(&mut self, text: &str, multiline: bool) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if multiline {
+            self.insert_str(&text);
+        } else {
+            self.insert_str(&text.replace('\n', " "));
+        }
+    }
+
+    // Horizontal offset that keeps the cursor inside a field of the given
+    // width.
+    pub fn scroll_x(&self, width: u16) -> u16 {
+        let (_, col) = self.line_col();
+        (col as u16).saturating_sub(width.saturating_sub(1))
+    }
+}
+
+pub fn prev_boundary(text: &str, i: usize) -> usize {
+    text[..i]
+        .grapheme_indices(true)
+        .next_back()
+        .map_or(0, |(j, _)| j)
+}
+
+pub fn next_boundary(text: &str, i: usize) -> usize {
+    text[i..].graphemes(true).next().map_or(i, |g| i + g.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punct,
+}
+
+fn class(g: &str) -> Class {
+    match g.chars().next() {
+        Some(c) if c.is_whitespace() => Class::Space,
+        Some(c) if c.is_alphanumeric() || c == '_' => Class::Word,
+        _ => Class::Punct,
+    }
+}
+
+// Start of the next word, as with vim's w.
+pub fn word_forward(text: &str, i: usize) -> usize {
+    let mut graphemes = text[i..]
+        .grapheme_indices(true)
+        .map(|(j, g)| (i + j, class(g)));
+    let Some((_, start)) = graphemes.next() else {
+        return i;
+    };
+    let mut seen_space = start == Class::Space;
+    for (j, cl) in graphemes {
+        if cl == Class::Space {
+            seen_space = true;
+        } else if seen_space || cl != start {
+            return j;
+        }
+    }
+    text.len()
+}
+
+// Start of the current or previous word, as with vim's b.
+pub fn word_back(text: &str, i: usize) -> usize {
+    let mut graphemes = text[..i]
+        .grapheme_indices(true)
+        .rev()
+        .map(|(j, g)| (j, class(g)))
+        .skip_while(|(_, cl)| *cl == Class::Space)
+        .peekable();
+    let Some(&(mut pos, word)) = graphemes.peek() else {
+        return 0;
+    };
+    for (j, cl) in graphemes {
+        if cl != word {
+            break;
+        }
+        pos = j;
+    }
+    pos
+}
+
+// Last grapheme of the current or next word, as with vim's e.
+pub fn word_end(text: &str, i: usize) -> usize {
+    let mut graphemes = text[i..]
+        .grapheme_indices(true)
+        .map(|(j, g)| (i + j, class(g)))
+        .skip(1)
+        .skip_while(|(_, cl)| *cl == Class::Space)
+        .peekable();
+    let Some(&(mut pos, word)) = graphemes.peek() else {
+        return i;
+    };
+    for (j, cl) in graphemes {
+        if cl != word {
+            break;
+        }
+        pos = j;
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // e + combining acute, a CJK word and a family emoji joined with ZWJs.
+    const MIXED: &str = "cafe\u{301} 日本語 👩\u{200d}👩\u{200d}👧";
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn on_boundary(input: &TextInput) -> bool {
+        let text = input.text();
+        input.cursor() == text.len()
+            || text
+                .grapheme_indices(true)
+                .any(|(i, _)| i == input.cursor())
+    }
+
+    #[test]
+    fn backspace_and_delete_remove_whole_graphemes() {
+        let mut input = TextInput::new(MIXED.to_string());
+        input.backspace();
+        assert_eq!(input.text(), "cafe\u{301} 日本語 ");
+        input.backspace();
+        input.backspace();
+        assert_eq!(input.text(), "cafe\u{301} 日本");
+        input.set_cursor(0);
+        input.right();
+        input.right();
+        input.right();
+        input.delete();
+        assert_eq!(input.text(), "caf 日本");
+        input.delete();
+        input.delete();
+        assert_eq!(input.text(), "caf本");
+        assert_eq!(input.cursor(), 3);
+    }
+
+    #[test]
+    fn typing_a_combining_mark_joins_the_previous_letter() {
+        let mut input = TextInput::new("e".to_string());
+        input.insert_char('\u{301}');
+        assert_eq!(input.text(), "e\u{301}");
+        assert_eq!(input.cursor(), 3);
+        input.left();
+        assert_eq!(input.cursor(), 0);
+        input.insert_char('日');
+        assert_eq!(input.text(), "日e\u{301}");
+        assert_eq!(input.cursor(), 3);
+    }
+
+    #[test]
+    fn cursor_steps_over_graphemes() {
+        let mut input = TextInput::new("e\u{301}日👩\u{200d}👩\u{200d}👧".to_string());
+        input.set_cursor(0);
+        let mut stops = vec![input.cursor()];
+        for _ in 0..4 {
+            input.right();
+            stops.push(input.cursor());
+        }
+        assert_eq!(stops, [0, 3, 6, 24, 24]);
+        input.left();
+        assert_eq!(input.cursor(), 6);
+        assert_eq!(input.line_col(), (0, 3));
+    }
+
+    #[test]
+    fn word_delete_stops_at_words_and_lines() {
+        let mut input = TextInput::new(MIXED.to_string());
+        let ctrl_w = key(KeyCode::Char('w'), KeyModifiers::CONTROL);
+        assert!(input.handle_key(ctrl_w, false));
+        assert_eq!(input.text(), "cafe\u{301} 日本語 ");
+        input.handle_key(ctrl_w, false);
+        assert_eq!(input.text(), "cafe\u{301} ");
+        input.handle_key(ctrl_w, false);
+        assert_eq!(input.text(), "");
+        input.handle_key(ctrl_w, false);
+        assert_eq!(input.text(), "");
+
+        let mut input = TextInput::new("one\ntwo".to_string());
+        input.delete_word_back();
+        assert_eq!(input.text(), "one\n");
+        input.delete_word_back();
+        assert_eq!(input.text(), "one");
+    }
+
+    #[test]
+    fn move_to_keeps_wide_characters_whole() {
+        let mut input = TextInput::new("日本語\nabcdef\n👩\u{200d}👩\u{200d}👧".to_string());
+        input.move_to(0, 3);
+        assert_eq!(input.cursor(), 3);
+        assert_eq!(input.line_col(), (0, 2));
+        input.move_to(1, 3);
+        assert_eq!(input.cursor(), 13);
+        input.move_to(0, 99);
+        assert_eq!(input.cursor(), 9);
+        input.move_to(2, 1);
+        assert_eq!(input.cursor(), 17);
+        input.move_to(5, 0);
+        assert_eq!(input.cursor(), 17);
+
+        input.move_to(1, 5);
+        input.handle_key(key(KeyCode::Up, KeyModifiers::NONE), true);
+        assert_eq!(input.line_col(), (0, 4));
+        input.handle_key(key(KeyCode::Down, KeyModifiers::NONE), true);
+        input.handle_key(key(KeyCode::Down, KeyModifiers::NONE), true);
+        assert_eq!(input.line_col(), (2, 2));
+    }
+
+    #[test]
+    fn paste_normalizes_line_breaks() {
+        let mut input = TextInput::new("日本".to_string());
+        input.left();
+        input.paste("a\r\nb\rc", false);
+        assert_eq!(input.text(), "日a b c本");
+        assert_eq!(input.cursor(), 8);
+
+        let mut input = TextInput::default();
+        input.paste("a\r\nb\rc", true);
+        assert_eq!(input.text(), "a\nb\nc");
+        assert_eq!(input.line_count(), 3);
+    }
+
+    #[test]
+    fn scroll_x_counts_display_columns() {
+        let input = TextInput::new("日本語日本語".to_string());
+        assert_eq!(input.scroll_x(5), 8);
+        assert_eq!(input.scroll_x(13), 0);
+        assert_eq!(input.scroll_x(0), 12);
+        assert_eq!(TextInput::default().scroll_x(0), 0);
+    }
+
+    #[test]
+    fn every_key_keeps_the_cursor_on_a_boundary() {
+        let plain = KeyModifiers::NONE;
+        let ctrl = KeyModifiers::CONTROL;
+        let keys = [
+            key(KeyCode::Char('x'), plain),
+            key(KeyCode::Char('\u{301}'), plain),
+            key(KeyCode::Char('w'), ctrl),
+            key(KeyCode::Char('u'), ctrl),
+            key(KeyCode::Char('z'), ctrl),
+            key(KeyCode::Char('y'), ctrl),
+            key(KeyCode::Enter, plain),
+            key(KeyCode::Backspace, plain),
+            key(KeyCode::Backspace, KeyModifiers::ALT),
+            key(KeyCode::Delete, plain),
+            key(KeyCode::Left, plain),
+            key(KeyCode::Right, plain),
+            key(KeyCode::Left, ctrl),
+            key(KeyCode::Right, ctrl),
+            key(KeyCode::Home, plain),
+            key(KeyCode::End, plain),
+            key(KeyCode::Up, plain),
+            key(KeyCode::Down, plain),
+        ];
+        let text = format!("{MIXED}\n{MIXED}\n");
+        let starts: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .collect();
+        for multiline in [false, true] {
+            for &start in &starts {
+                for first in &keys {
+                    let mut input = TextInput::new(text.clone());
+                    input.set_cursor(start);
+                    for k in [first].into_iter().chain(&keys) {
+                        input.handle_key(*k, multiline);
+                        assert!(on_boundary(&input), "{k:?} at {start}");
+                        input.scroll_x(4);
+                    }
+                }
+            }
+        }
+    }
+}
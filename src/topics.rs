@@ -0,0 +1,392 @@
+use crate::{deck, stats::History, FlashCardEngine};
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Operations on whole topics, i.e. the directories below the topics root.
+
+pub const TITLE_FILE: &str = "topic.toml";
+const MAX_TITLE: usize = 64;
+
+// A topic is stored in a directory named after a slug of its title; the title
+// itself, as typed, is kept in topic.toml. Directories without one (made by
+// hand or by older versions) use the directory name as the title. Topics can
+// be nested: `dir` is the path below the topics root, e.g. "science/earth".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topic {
+    pub dir: String,
+    pub title: String,
+}
+
+impl Topic {
+    pub fn parent(&self) -> Option<&str> {
+        self.dir.rsplit_once('/').map(|(parent, _)| parent)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.dir.matches('/').count()
+    }
+
+    pub fn is_ancestor_of(&self, other: &Topic) -> bool {
+        other
+            .dir
+            .strip_prefix(&self.dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TitleFile {
+    title: String,
+}
+
+// All topics in tree order: every topic is followed by its subtopics, and
+// siblings are sorted by title.
+pub fn list(topics_dir: &Path) -> Result<Vec<Topic>> {
+    fs::create_dir_all(topics_dir)?;
+    let mut topics = Vec::new();
+    list_into(topics_dir, None, &mut topics)?;
+    Ok(topics)
+}
+
+fn list_into(topics_dir: &Path, parent: Option<&str>, topics: &mut Vec<Topic>) -> Result<()> {
+    let path = parent.map_or(topics_dir.to_path_buf(), |p| topics_dir.join(p));
+    let mut children: Vec<Topic> = fs::read_dir(path)?
+        .filter_map(|res| res.ok())
+        .filter(|e| e.file_type().map(|ft| ft.is_dir()).unwrap_or(false))
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.'))
+        .map(|name| {
+            let dir = parent.map_or(name.clone(), |p| format!("{p}/{name}"));
+            Topic {
+                title: read_title(&topics_dir.join(&dir)).unwrap_or(name),
+                dir,
+            }
+        })
+        .collect();
+    children.sort_by_key(|t| t.title.to_lowercase());
+    for topic in children {
+        let dir = topic.dir.clone();
+        topics.push(topic);
+        list_into(topics_dir, Some(&dir), topics)?;
+    }
+    Ok(())
+}
+
+// Titles of the topic and its ancestors, e.g. "Science/Earth".
+pub fn title_path(topics: &[Topic], topic: &Topic) -> String {
+    let mut path = topic.title.clone();
+    let mut parent = topic.parent();
+    while let Some(dir) = parent {
+        let Some(t) = topics.iter().find(|t| t.dir == dir) else {
+            break;
+        };
+        path = format!("{}/{path}", t.title);
+        parent = t.parent();
+    }
+    path
+}
+
+fn read_title(dir: &Path) -> Option<String> {
+    let text = fs::read_to_string(dir.join(TITLE_FILE)).ok()?;
+    let file: TitleFile = toml::from_str(&text).ok()?;
+    Some(file.title).filter(|t| !t.trim().is_empty())
+}
+
+fn write_title(dir: &Path, title: &str) -> Result<()> {
+    let file = TitleFile {
+        title: title.to_string(),
+    };
+    crate::write_atomic(&dir.join(TITLE_FILE), &toml::to_string(&file)?)
+}
+
+// Finds a topic by directory or, ignoring case, by title path such as
+// "Science/Earth". A top-level topic can also be found by its title alone.
+pub fn find(topics_dir: &Path, name: &str) -> Result<Topic> {
+    let topics = list(topics_dir)?;
+    let wanted = name.trim().trim_matches('/').to_lowercase();
+    topics
+        .iter()
+        .find(|t| t.dir == name || title_path(&topics, t).to_lowercase() == wanted)
+        .cloned()
+        .with_context(|| format!("No topic named {name:?}"))
+}
+
+// Creates whichever topics along a title path do not exist yet.
+pub fn find_or_create(topics_dir: &Path, name: &str) -> Result<Topic> {
+    if let Ok(topic) = find(topics_dir, name) {
+        return Ok(topic);
+    }
+    let mut parent: Option<Topic> = None;
+    for title in name.split('/').filter(|t| !t.trim().is_empty()) {
+        let topic = list(topics_dir)?.into_iter().find(|t| {
+            t.parent() == parent.as_ref().map(|p| p.dir.as_str())
+                && t.title.to_lowercase() == title.trim().to_lowercase()
+        });
+        parent = Some(match topic {
+            Some(topic) => topic,
+            None => create(topics_dir, parent.as_ref(), title)?,
+        });
+    }
+    parent.with_context(|| format!("No topic named {name:?}"))
+}
+
+// Lowercase letters and digits separated by dashes, e.g. "Rust: Traits" ->
+// "rust-traits". Never contains a path separator or a dot.
+pub fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+// Checks a title typed for a new or renamed topic below `parent` (`except`:
+// the directory of the topic being renamed) and returns the directory it will
+// be stored in. Titles only need to be unique among siblings.
+pub fn dir_for(
+    topics: &[Topic],
+    parent: Option<&str>,
+    title: &str,
+    except: Option<&str>,
+) -> Result<String> {
+    let title = title.trim();
+    if title.is_empty() {
+        bail!("Enter a name");
+    }
+    if title.chars().count() > MAX_TITLE {
+        bail!("Names can be at most {MAX_TITLE} characters long");
+    }
+    if title.chars().any(char::is_control) {
+        bail!("Names cannot contain control characters");
+    }
+    if title.contains('/') {
+        bail!("Names cannot contain '/'");
+    }
+    let others: Vec<&Topic> = topics
+        .iter()
+        .filter(|t| t.parent() == parent && Some(t.dir.as_str()) != except)
+        .collect();
+    if others
+        .iter()
+        .any(|t| t.title.to_lowercase() == title.to_lowercase())
+    {
+        bail!("A topic named {title:?} already exists");
+    }
+    let base = slug(title);
+    if base.is_empty() {
+        bail!("Names need at least one letter or digit");
+    }
+    let base = parent.map_or(base.clone(), |p| format!("{p}/{base}"));
+    // Directories are compared ignoring case for case-insensitive file systems.
+    let taken = |dir: &str| {
+        others
+            .iter()
+            .any(|t| t.dir.to_lowercase() == dir.to_lowercase())
+    };
+    let mut dir = base.clone();
+    let mut n = 2;
+    while taken(&dir) {
+        dir = format!("{base}-{n}");
+        n += 1;
+    }
+    Ok(dir)
+}
+
+pub fn create(topics_dir: &Path, parent: Option<&Topic>, title: &str) -> Result<Topic> {
+    let parent = parent.map(|p| p.dir.as_str());
+    let dir = dir_for(&list(topics_dir)?, parent, title, None)?;
+    let path = topics_dir.join(&dir);
+    fs::create_dir_all(&path).with_context(|| format!("Creating {}", path.display()))?;
+    write_title(&path, title.trim())?;
+    Ok(Topic {
+        dir,
+        title: title.trim().to_string(),
+    })
+}
+
+fn existing(topics_dir: &Path, topic: &Topic) -> Result<PathBuf> {
+    let path = topics_dir.join(&topic.dir);
+    if !path.is_dir() {
+        bail!("No topic named {:?}", topic.title);
+    }
+    Ok(path)
+}
+
+pub fn rename(topics_dir: &Path, from: &Topic, title: &str) -> Result<Topic> {
+    let src = existing(topics_dir, from)?;
+    let dir = dir_for(&list(topics_dir)?, from.parent(), title, Some(&from.dir))?;
+    let dst = topics_dir.join(&dir);
+    if dir != from.dir {
+        fs::rename(&src, &dst).with_context(|| format!("Renaming {}", from.title))?;
+    }
+    write_title(&dst, title.trim())?;
+    Ok(Topic {
+        dir,
+        title: title.trim().to_string(),
+    })
+}
+
+// Copies the deck along with its schedule, statistics, history and subtopics.
+pub fn duplicate(topics_dir: &Path, from: &Topic, title: &str) -> Result<Topic> {
+    let src = existing(topics_dir, from)?;
+    let dir = dir_for(&list(topics_dir)?, from.parent(), title, None)?;
+    let dst = topics_dir.join(&dir);
+    copy_dir(&src, &dst)
+        .and_then(|()| write_title(&dst, title.trim()))
+        .map_err(|e| {
+            let _ = fs::remove_dir_all(&dst);
+            e.context(format!("Copying {}", from.title))
+        })?;
+    Ok(Topic {
+        dir,
+        title: title.trim().to_string(),
+    })
+}
+
+// Topics are never deleted outright; they are moved to the trash directory,
+// from where they can be copied back by hand.
+pub fn trash(topics_dir: &Path, trash_dir: &Path, topic: &Topic) -> Result<PathBuf> {
+    let src = existing(topics_dir, topic)?;
+    fs::create_dir_all(trash_dir).with_context(|| format!("Creating {}", trash_dir.display()))?;
+    let ts = Local::now().format("%Y%m%d-%H%M%S");
+    let dst = trash_dir.join(format!("{}-{ts}", topic.dir.replace('/', "-")));
+    if fs::rename(&src, &dst).is_err() {
+        // The trash may be on another file system than the topics.
+        copy_dir(&src, &dst).with_context(|| format!("Moving {} to the trash", topic.title))?;
+        fs::remove_dir_all(&src).with_context(|| format!("Removing {}", src.display()))?;
+    }
+    Ok(dst)
+}
+
+// Appends the cards of `from` to `into`, keeping their schedules, statistics
+// and session history, then trashes `from`. Returns the number of cards added.
+pub fn merge(topics_dir: &Path, trash_dir: &Path, from: &Topic, into: &Topic) -> Result<usize> {
+    if from.dir == into.dir {
+        bail!("Cannot merge {} into itself", from.title);
+    }
+    if list(topics_dir)?.iter().any(|t| from.is_ancestor_of(t)) {
+        bail!("{} has subtopics; merge or move them first", from.title);
+    }
+    let src = FlashCardEngine::edit_dir(&existing(topics_dir, from)?)?;
+    let mut dst = FlashCardEngine::edit_dir(&existing(topics_dir, into)?)?;
+    let mut added = 0;
+    let src_history = History::load(&src.history_file())?;
+    for (mut card, schedule) in src.cards.into_iter().zip(src.schedule) {
+        let id = card.id.clone();
+        // A duplicated topic shares card ids with the original.
+        if let Some(same) = dst.cards.iter().find(|c| c.id == card.id) {
+            if *same == card {
+                continue;
+            }
+            card.id = deck::new_id();
+        }
+        dst.stats.copy(&src.stats, &id, &card.id);
+        dst.cards.push(card);
+        dst.schedule.push(schedule);
+        dst.source.push(0);
+        added += 1;
+    }
+    dst.persist_edits()?;
+    dst.save_stats(0)?;
+    let mut history = History::load(&dst.history_file())?;
+    history.sessions.extend(src_history.sessions);
+    history.sessions.sort_by_key(|s| s.finished);
+    history.save(&dst.history_file())?;
+    trash(topics_dir, trash_dir, from)?;
+    Ok(added)
+}
+
+pub fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic(dir: &str, title: &str) -> Topic {
+        Topic {
+            dir: dir.into(),
+            title: title.into(),
+        }
+    }
+
+    #[test]
+    fn slug_keeps_letters_and_digits() {
+        assert_eq!(slug("Machine Learning 101"), "machine-learning-101");
+        assert_eq!(slug("  C++ -- Rust!  "), "c-rust");
+        assert_eq!(slug("Ärzte & Über"), "ärzte-über");
+        assert_eq!(slug("日本語"), "日本語");
+        assert_eq!(slug(".."), "");
+        assert_eq!(slug("../etc"), "etc");
+        assert_eq!(slug("a\tb\nc"), "a-b-c");
+    }
+
+    #[test]
+    fn dir_for_rejects_unusable_names() {
+        let err = |title: &str| dir_for(&[], None, title, None).unwrap_err().to_string();
+        assert_eq!(err("   "), "Enter a name");
+        assert_eq!(err(".."), "Names need at least one letter or digit");
+        assert_eq!(err("a/b"), "Names cannot contain '/'");
+        assert_eq!(err("../a"), "Names cannot contain '/'");
+        assert_eq!(err("a\tb"), "Names cannot contain control characters");
+        assert_eq!(err("a\u{7f}"), "Names cannot contain control characters");
+        assert!(dir_for(&[], None, &"x".repeat(MAX_TITLE + 1), None).is_err());
+        assert_eq!(
+            dir_for(&[], None, &"x".repeat(MAX_TITLE), None)
+                .unwrap()
+                .len(),
+            MAX_TITLE
+        );
+    }
+
+    #[test]
+    fn dir_for_avoids_duplicates_among_siblings() {
+        let topics = [
+            topic("spanish", "Spanish"),
+            topic("AI", "AI"),
+            topic("AI/ml", "Machine learning"),
+            topic("science", "Science"),
+            topic("science/spanish", "Spanish"),
+        ];
+        let dir = |parent, title: &str| dir_for(&topics, parent, title, None);
+        assert!(dir(None, "spanish").is_err());
+        assert!(dir(None, " SPANISH ").is_err());
+        assert_eq!(dir(Some("science"), "Earth").unwrap(), "science/earth");
+        // Different titles with the same slug get numbered directories, also
+        // when only the case differs.
+        assert_eq!(dir(None, "Spanish!").unwrap(), "spanish-2");
+        assert_eq!(dir(None, "ai?").unwrap(), "ai-2");
+        assert_eq!(dir(Some("AI"), "ML").unwrap(), "AI/ml-2");
+        assert_eq!(
+            dir(Some("AI"), "Deep learning").unwrap(),
+            "AI/deep-learning"
+        );
+        // Titles only need to be unique among siblings.
+        assert!(dir(Some("AI"), "Spanish").is_ok());
+        // Renaming a topic may keep its own title and directory.
+        assert_eq!(
+            dir_for(&topics, None, "Spanish", Some("spanish")).unwrap(),
+            "spanish"
+        );
+    }
+}
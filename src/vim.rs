@@ -0,0 +1,200 @@
+use crate::textinput::{self, TextInput};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Insert,
+}
+
+impl Mode {
+    pub fn label(self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListMotion {
+    Up,
+    Down,
+    Top,
+    Bottom,
+}
+
+// Vim-style state shared by the lists and the text fields. Text fields start
+// in insert mode so answers can be typed right away.
+#[derive(Debug, Clone)]
+pub struct Vim {
+    pub mode: Mode,
+    // First key of a two-key command such as gg or dd.
+    pending: Option<char>,
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Insert,
+            pending: None,
+        }
+    }
+}
+
+fn plain_char(key: KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+impl Vim {
+    // Called whenever a text field is filled with new content.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn list_motion(&mut self, key: KeyEvent) -> Option<ListMotion> {
+        let c = plain_char(key)?;
+        let pending = self.pending.take();
+        match c {
+            'j' => Some(ListMotion::Down),
+            'k' => Some(ListMotion::Up),
+            'G' => Some(ListMotion::Bottom),
+            'g' if pending == Some('g') => Some(ListMotion::Top),
+            'g' => {
+                self.pending = Some('g');
+                None
+            }
+            _ => None,
+        }
+    }
+
+    // Handles a key for a text field. Returns false when the key is not a
+    // vim command, so the caller treats it as usual (typing, actions).
+    pub fn edit(&mut self, key: KeyEvent, input: &mut TextInput) -> bool {
+        if self.mode == Mode::Insert {
+            if key.code == KeyCode::Esc {
+                self.mode = Mode::Normal;
+                input.checkpoint();
+                input.set_cursor(on_char(input, prev(input)));
+                return true;
+            }
+            return false;
+        }
+        let Some(c) = plain_char(key) else {
+            return false;
+        };
+        let text = input.text();
+        let cursor = input.cursor();
+        let pending = self.pending.take();
+        match (pending, c) {
+            (_, 'h') => input.set_cursor(on_char(input, prev(input))),
+            (_, 'l') => input.set_cursor(on_char(input, next(input))),
+            (_, 'j') | (_, 'k') => {
+                let (row, col) = input.line_col();
+                if c == 'j' && row + 1 < input.line_count() {
+                    input.move_to(row + 1, col);
+                } else if c == 'k' && row > 0 {
+                    input.move_to(row - 1, col);
+                }
+            }
+            (_, '0') => input.set_cursor(input.line_start()),
+            (_, '$') => input.set_cursor(on_char(input, input.line_end())),
+            (_, 'w') => {
+                let pos = textinput::word_forward(text, cursor);
+                input.set_cursor(on_char(input, pos));
+            }
+            (_, 'b') => input.set_cursor(textinput::word_back(text, cursor)),
+            (_, 'e') => input.set_cursor(textinput::word_end(text, cursor)),
+            (Some('g'), 'g') => input.set_cursor(0),
+            (_, 'G') => {
+                let last = input.line_count() - 1;
+                input.move_to(last, 0);
+            }
+            (_, 'i') => self.insert(input),
+            (_, 'a') => {
+                if cursor < input.line_end() {
+                    input.set_cursor(next(input));
+                }
+                self.insert(input);
+            }
+            (_, 'I') => {
+                input.set_cursor(input.line_start());
+                self.insert(input);
+            }
+            (_, 'A') => {
+                input.set_cursor(input.line_end());
+                self.insert(input);
+            }
+            (_, 'o') => {
+                let at = input.line_end();
+                input.replace(at..at, "\n");
+                self.mode = Mode::Insert;
+            }
+            (_, 'O') => {
+                let at = input.line_start();
+                input.replace(at..at, "\n");
+                input.set_cursor(at);
+                self.mode = Mode::Insert;
+            }
+            (_, 'x') if cursor < input.line_end() => {
+                input.replace(cursor..next(input), "");
+                input.set_cursor(on_char(input, cursor));
+            }
+            (Some('d'), 'd') => {
+                let start = input.line_start();
+                let end = input.line_end();
+                let range = if end < text.len() {
+                    start..end + 1
+                } else {
+                    start.saturating_sub(1)..end
+                };
+                input.replace(range, "");
+                let row = input.line_col().0;
+                input.move_to(row, 0);
+            }
+            (_, 'u') => {
+                input.undo();
+                input.set_cursor(on_char(input, input.cursor()));
+            }
+            (_, 'g') | (_, 'd') => self.pending = Some(c),
+            _ => {}
+        }
+        true
+    }
+
+    fn insert(&mut self, input: &mut TextInput) {
+        input.checkpoint();
+        self.mode = Mode::Insert;
+    }
+}
+
+fn prev(input: &TextInput) -> usize {
+    textinput::prev_boundary(input.text(), input.cursor())
+}
+
+fn next(input: &TextInput) -> usize {
+    textinput::next_boundary(input.text(), input.cursor())
+}
+
+// In normal mode the cursor rests on a character, never past the end of the
+// line (except on an empty line).
+fn on_char(input: &TextInput, pos: usize) -> usize {
+    let text = input.text();
+    let pos = pos.min(text.len());
+    let start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[pos..].find('\n').map_or(text.len(), |i| pos + i);
+    if pos >= end && end > start {
+        textinput::prev_boundary(text, end)
+    } else {
+        pos.max(start)
+    }
+}